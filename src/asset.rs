@@ -1,13 +1,57 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::collections::HashMap;
 
-use crate::{asset_ident::AssetIdent, state::TargetState};
+use crate::{
+    asset_ident::AssetIdent,
+    config::InputConfig,
+    state::{AssetMetadata, TargetState},
+};
 
 #[derive(Debug)]
 pub struct Asset {
     /// A unique identifier for this asset in the project.
     pub ident: AssetIdent,
-    pub path: PathBuf,
     pub contents: debug_ignore::DebugIgnore<Vec<u8>>,
     pub hash: String,
+
+    /// The input that matched this asset, with its processing options.
+    pub input: InputConfig,
+
     pub targets: HashMap<String, TargetState>,
+
+    /// Set by preprocessing, or carried over from the previous state if unchanged.
+    pub metadata: Option<AssetMetadata>,
+
+    /// Set if the asset is packed into a spritesheet instead of being synced on its own.
+    pub packed: bool,
+
+    /// The density of this variant, for images from inputs with `dpi_scales`.
+    pub dpi_scale: Option<u32>,
+
+    /// Set for generated DPI variants, whose `contents` are their source's
+    /// until preprocessing shrinks them by this factor.
+    pub dpi_rescale: Option<f64>,
+
+    /// Bytes removed by PNG optimization, set by preprocessing.
+    pub bytes_saved: u64,
+
+    /// Set once preprocessing has run, so `contents` isn't processed a second time.
+    pub preprocessed: bool,
+}
+
+impl Asset {
+    pub fn new(ident: AssetIdent, contents: Vec<u8>, hash: String, input: &InputConfig) -> Self {
+        Self {
+            ident,
+            contents: contents.into(),
+            hash,
+            input: input.clone(),
+            targets: HashMap::new(),
+            metadata: None,
+            packed: false,
+            dpi_scale: None,
+            dpi_rescale: None,
+            bytes_saved: 0,
+            preprocessed: false,
+        }
+    }
 }
@@ -7,6 +7,9 @@ use std::{
 use rbxcloud::rbx::assets::AssetType;
 use serde::{Deserialize, Serialize};
 
+/// Image formats Roblox doesn't accept, which are converted to PNG before syncing.
+const CONVERTED_EXTENSIONS: &[&str] = &["bmp", "tga", "webp", "gif", "psd", "svg"];
+
 /// Represents a path to an asset inside a project.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -29,11 +32,18 @@ impl AssetIdent {
         let ident = AssetIdent(displayed.into());
 
         // Make sure this file maps to a valid asset type
-        AssetType::try_from_extension(&ident.extension().unwrap_or_default())?;
+        if !ident.is_converted() {
+            AssetType::try_from_extension(&ident.extension().unwrap_or_default())?;
+        }
 
         Ok(ident)
     }
 
+    /// Creates an ident for a file generated by Runway, such as a spritesheet.
+    pub fn generated(path: &str) -> Self {
+        AssetIdent(path.into())
+    }
+
     pub fn with_cache_bust(&self, cb: &str) -> PathBuf {
         let mut p: PathBuf = self.to_string().into();
         let mut file_name = p.file_stem().unwrap_or_default().to_owned();
@@ -41,6 +51,11 @@ impl AssetIdent {
         file_name.push(cb);
         file_name.push(".");
         file_name.push(p.extension().unwrap_or_default());
+        // Converted images are PNGs, but keep their original extension so they don't
+        // collide with other files that have the same name
+        if self.is_converted() {
+            file_name.push(".png");
+        }
         p.set_file_name(file_name);
         p
     }
@@ -55,10 +70,50 @@ impl AssetIdent {
         p.extension().map(|e| e.to_string_lossy().to_string())
     }
 
+    /// Whether this is an image in a format that's converted to PNG before syncing.
+    pub fn is_converted(&self) -> bool {
+        self.extension()
+            .is_some_and(|e| CONVERTED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+    }
+
     pub fn asset_type(&self) -> AssetType {
+        if self.is_converted() {
+            return AssetType::DecalPng;
+        }
+
         // We can unwrap here because we already checked in new()
         AssetType::try_from_extension(&self.extension().unwrap_or_default()).unwrap()
     }
+
+    pub fn kind(&self) -> AssetKind {
+        match self.asset_type() {
+            AssetType::DecalPng
+            | AssetType::DecalJpeg
+            | AssetType::DecalBmp
+            | AssetType::DecalTga => AssetKind::Image,
+            AssetType::AudioMp3 | AssetType::AudioOgg => AssetKind::Audio,
+            _ => AssetKind::Model,
+        }
+    }
+}
+
+/// A broad category of asset types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetKind {
+    Image,
+    Audio,
+    Model,
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetKind::Image => write!(f, "image"),
+            AssetKind::Audio => write!(f, "audio"),
+            AssetKind::Model => write!(f, "model"),
+        }
+    }
 }
 
 impl AsRef<str> for AssetIdent {
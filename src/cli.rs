@@ -25,6 +25,8 @@ pub enum Subcommand {
     Sync(SyncOptions),
     Watch(WatchOptions),
     Codegen(CodegenOptions),
+    Check(CheckOptions),
+    Catalog(CatalogOptions),
 }
 
 #[derive(Args, Debug)]
@@ -55,6 +57,23 @@ pub struct CodegenOptions {
     pub project: ProjectOptions,
 }
 
+#[derive(Args, Debug)]
+pub struct CheckOptions {
+    #[command(flatten)]
+    pub project: ProjectOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct CatalogOptions {
+    /// Path to config file or directory containing config file.
+    #[arg(short, long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Path to write the catalog to. Defaults to `runway-catalog.html` in the project.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ProjectOptions {
     /// Path to config file or directory containing config file.
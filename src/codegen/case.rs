@@ -0,0 +1,172 @@
+//! Transforms path segments into keys that are easier to use from code.
+
+use serde::Deserialize;
+
+use crate::config::CodegenConfig;
+
+/// A case convention to convert keys to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    /// `closeButton`
+    Camel,
+    /// `CloseButton`
+    Pascal,
+    /// `close_button`
+    Snake,
+    /// `close-button`
+    Kebab,
+}
+
+/// Applies a codegen output's case, sanitization, and digit prefix options to one path segment.
+pub(super) fn transform_segment(segment: &str, config: &CodegenConfig) -> String {
+    let mut out = match config.case {
+        Some(case) => convert_case(segment, case),
+        None if config.sanitize => segment
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '_')
+            .collect(),
+        None => segment.to_string(),
+    };
+
+    // Don't remove every character from a segment
+    if out.is_empty() {
+        out = segment.to_string();
+    }
+
+    if let Some(prefix) = &config.digit_prefix {
+        if out.starts_with(|c: char| c.is_ascii_digit()) {
+            out.insert_str(0, prefix);
+        }
+    }
+
+    out
+}
+
+fn convert_case(segment: &str, case: KeyCase) -> String {
+    let words = split_words(segment);
+
+    match case {
+        KeyCase::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                if i == 0 {
+                    w.to_lowercase()
+                } else {
+                    capitalize(w)
+                }
+            })
+            .collect(),
+        KeyCase::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+        KeyCase::Snake => words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("_"),
+        KeyCase::Kebab => words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-"),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+// Splits a segment into words at whitespace, punctuation, and changes in case,
+// e.g. `HTTPServer close-button` becomes `HTTP`, `Server`, `close`, `button`
+fn split_words(segment: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    let chars = segment.chars().collect::<Vec<_>>();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if let Some(prev) = word.chars().next_back() {
+            let next = chars.get(i + 1);
+
+            // `aB` starts a new word at `B`, and `ABc` starts a new word at `B`
+            let lower_to_upper = (prev.is_lowercase() || prev.is_numeric()) && c.is_uppercase();
+            let acronym_end =
+                prev.is_uppercase() && c.is_uppercase() && next.is_some_and(|n| n.is_lowercase());
+
+            if lower_to_upper || acronym_end {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::config;
+
+    fn transform(segment: &str, options: &str) -> String {
+        transform_segment(
+            segment,
+            &config(&("format = \"luau\"\n".to_string() + options)),
+        )
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(transform("Close Button", "case = \"camel\""), "closeButton");
+        assert_eq!(
+            transform("close_button", "case = \"pascal\""),
+            "CloseButton"
+        );
+        assert_eq!(transform("closeButton", "case = \"snake\""), "close_button");
+        assert_eq!(
+            transform("Close  Button!", "case = \"kebab\""),
+            "close-button"
+        );
+        assert_eq!(transform("HTTPServer", "case = \"snake\""), "http_server");
+        assert_eq!(transform("icon2X", "case = \"snake\""), "icon2_x");
+    }
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(
+            transform("Close Button (1)", "sanitize = true"),
+            "CloseButton1"
+        );
+        assert_eq!(transform("!!!", "sanitize = true"), "!!!");
+        assert_eq!(transform("snake_case", "sanitize = true"), "snake_case");
+    }
+
+    #[test]
+    fn digit_prefix() {
+        assert_eq!(transform("1st", "digit_prefix = \"_\""), "_1st");
+        assert_eq!(transform("first", "digit_prefix = \"_\""), "first");
+        assert_eq!(
+            transform("2 Player", "case = \"camel\"\ndigit_prefix = \"n\""),
+            "n2Player"
+        );
+    }
+}
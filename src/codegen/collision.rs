@@ -0,0 +1,333 @@
+//! Finds codegen keys that would overwrite or shadow each other and resolves
+//! them according to a codegen output's [`CollisionPolicy`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::Deserialize;
+
+use crate::asset_ident::AssetIdent;
+
+use super::CodegenError;
+
+/// What to do when two assets map to the same key in a codegen output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Fail to generate the output.
+    #[default]
+    Error,
+
+    /// Keep the file extension in the keys of colliding assets.
+    KeepExtension,
+
+    /// Append a numbered suffix to the keys of colliding assets.
+    Suffix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionKind {
+    /// Both assets map to exactly the same key.
+    Duplicate,
+
+    /// The assets' keys only differ by case.
+    Case,
+
+    /// One asset's key is also used as a folder by the other.
+    FileFolder,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyCollision {
+    pub kind: CollisionKind,
+    pub key: String,
+    pub first: AssetIdent,
+    pub second: AssetIdent,
+}
+
+impl fmt::Display for KeyCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            CollisionKind::Duplicate => write!(
+                f,
+                "'{}' is generated by both '{}' and '{}'",
+                self.key, self.first, self.second
+            ),
+            CollisionKind::Case => write!(
+                f,
+                "'{}' from '{}' differs only by case from the key for '{}'",
+                self.key, self.second, self.first
+            ),
+            CollisionKind::FileFolder => write!(
+                f,
+                "'{}' is used as a folder by '{}' and as an asset by '{}'",
+                self.key, self.first, self.second
+            ),
+        }
+    }
+}
+
+/// An asset and the path of keys it will be generated at.
+pub(super) struct KeyedAsset<'a> {
+    pub ident: &'a AssetIdent,
+    pub key: Vec<String>,
+}
+
+impl KeyedAsset<'_> {
+    fn joined(&self) -> String {
+        self.key.join("/")
+    }
+
+    fn folders(&self) -> impl Iterator<Item = String> + '_ {
+        (1..self.key.len()).map(|len| self.key[..len].join("/"))
+    }
+}
+
+/// Finds every pair of assets whose keys would collide, comparing keys case-insensitively.
+pub(super) fn find_collisions(entries: &[KeyedAsset]) -> Vec<KeyCollision> {
+    let mut collisions = Vec::new();
+
+    // Lowercase key -> (index of the first entry using it, exact key)
+    let mut folders: HashMap<String, (usize, String)> = HashMap::new();
+    let mut leaves: HashMap<String, (usize, String)> = HashMap::new();
+
+    // Folders whose differently-cased spelling has already been reported
+    let mut reported_folders: HashSet<String> = HashSet::new();
+
+    // Collect every folder first so files that sort before a folder with the same name are caught
+    for (index, entry) in entries.iter().enumerate() {
+        for folder in entry.folders() {
+            let lower = folder.to_lowercase();
+
+            match folders.get(&lower) {
+                Some((first, exact)) => {
+                    if *exact != folder && reported_folders.insert(folder.clone()) {
+                        collisions.push(KeyCollision {
+                            kind: CollisionKind::Case,
+                            key: folder,
+                            first: entries[*first].ident.clone(),
+                            second: entry.ident.clone(),
+                        });
+                    }
+                }
+                None => {
+                    folders.insert(lower, (index, folder));
+                }
+            }
+        }
+    }
+
+    for (index, entry) in entries.iter().enumerate() {
+        let key = entry.joined();
+        let lower = key.to_lowercase();
+
+        if let Some((first, _)) = folders.get(&lower) {
+            collisions.push(KeyCollision {
+                kind: CollisionKind::FileFolder,
+                key: key.clone(),
+                first: entries[*first].ident.clone(),
+                second: entry.ident.clone(),
+            });
+        }
+
+        match leaves.get(&lower) {
+            Some((first, exact)) => collisions.push(KeyCollision {
+                kind: if *exact == key {
+                    CollisionKind::Duplicate
+                } else {
+                    CollisionKind::Case
+                },
+                key,
+                first: entries[*first].ident.clone(),
+                second: entry.ident.clone(),
+            }),
+            None => {
+                leaves.insert(lower, (index, key));
+            }
+        }
+    }
+
+    collisions
+}
+
+/// Changes the keys of colliding entries according to `policy`. `rekey` should return
+/// an asset's key with its file extension kept.
+///
+/// Returns an error listing every collision that couldn't be resolved.
+pub(super) fn resolve_collisions<F>(
+    entries: &mut [KeyedAsset],
+    policy: CollisionPolicy,
+    rekey: F,
+) -> Result<(), CodegenError>
+where
+    F: Fn(&AssetIdent) -> Vec<String>,
+{
+    match policy {
+        CollisionPolicy::Error => {}
+        CollisionPolicy::KeepExtension => {
+            let colliding = find_collisions(entries)
+                .into_iter()
+                .flat_map(|c| [c.first, c.second])
+                .collect::<HashSet<_>>();
+
+            for entry in entries.iter_mut() {
+                if colliding.contains(entry.ident) {
+                    entry.key = rekey(entry.ident);
+                }
+            }
+        }
+        CollisionPolicy::Suffix => {
+            let folders = entries
+                .iter()
+                .flat_map(|e| e.folders())
+                .map(|f| f.to_lowercase())
+                .collect::<HashSet<_>>();
+
+            // Don't let a suffixed key take the key of another asset
+            let natural = entries
+                .iter()
+                .map(|e| e.joined().to_lowercase())
+                .collect::<HashSet<_>>();
+
+            let mut taken = HashSet::new();
+
+            for entry in entries.iter_mut() {
+                let Some(base) = entry.key.last().cloned() else {
+                    continue;
+                };
+
+                let mut n = 1;
+                loop {
+                    let lower = entry.joined().to_lowercase();
+                    let reserved = n > 1 && natural.contains(&lower);
+
+                    if !folders.contains(&lower) && !reserved && taken.insert(lower) {
+                        break;
+                    }
+
+                    n += 1;
+                    *entry.key.last_mut().unwrap() = format!("{base}_{n}");
+                }
+            }
+        }
+    }
+
+    let collisions = find_collisions(entries);
+    if collisions.is_empty() {
+        Ok(())
+    } else {
+        Err(CodegenError::Collisions { collisions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn keyed<'a>(idents: &'a [AssetIdent], keys: &[&str]) -> Vec<KeyedAsset<'a>> {
+        idents
+            .iter()
+            .zip(keys)
+            .map(|(ident, key)| KeyedAsset {
+                ident,
+                key: key.split('/').map(str::to_string).collect(),
+            })
+            .collect()
+    }
+
+    fn idents(paths: &[&str]) -> Vec<AssetIdent> {
+        paths
+            .iter()
+            .map(|p| AssetIdent::from_paths(Path::new(""), Path::new(p)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn finds_every_kind() {
+        let idents = idents(&["a/icon.jpg", "a/icon.png", "a/Icon.png", "b.png", "b/c.png"]);
+        let entries = keyed(&idents, &["a/icon", "a/icon", "a/Icon", "b", "b/c"]);
+
+        let kinds = find_collisions(&entries)
+            .into_iter()
+            .map(|c| (c.kind, c.first.to_string(), c.second.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    CollisionKind::Duplicate,
+                    "a/icon.jpg".to_string(),
+                    "a/icon.png".to_string()
+                ),
+                (
+                    CollisionKind::Case,
+                    "a/icon.jpg".to_string(),
+                    "a/Icon.png".to_string()
+                ),
+                (
+                    CollisionKind::FileFolder,
+                    "b/c.png".to_string(),
+                    "b.png".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_folder_case() {
+        let idents = idents(&["UI/a.png", "ui/b.png"]);
+        let entries = keyed(&idents, &["UI/a", "ui/b"]);
+
+        let collisions = find_collisions(&entries);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].kind, CollisionKind::Case);
+        assert_eq!(collisions[0].key, "ui");
+    }
+
+    #[test]
+    fn keep_extension() {
+        let idents = idents(&["icon.jpg", "icon.png", "other.png"]);
+        let mut entries = keyed(&idents, &["icon", "icon", "other"]);
+
+        resolve_collisions(&mut entries, CollisionPolicy::KeepExtension, |i| {
+            vec![i.to_string()]
+        })
+        .unwrap();
+
+        let keys = entries.iter().map(|e| e.joined()).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["icon.jpg", "icon.png", "other"]);
+    }
+
+    #[test]
+    fn suffix() {
+        let idents = idents(&["b.png", "b/c.png", "icon.jpg", "icon.png", "icon_2.png"]);
+        let mut entries = keyed(&idents, &["b", "b/c", "icon", "icon", "icon_2"]);
+
+        resolve_collisions(&mut entries, CollisionPolicy::Suffix, |i| {
+            vec![i.to_string()]
+        })
+        .unwrap();
+
+        let keys = entries.iter().map(|e| e.joined()).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["b_2", "b/c", "icon", "icon_3", "icon_2"]);
+    }
+
+    #[test]
+    fn error() {
+        let idents = idents(&["icon.jpg", "icon.png"]);
+        let mut entries = keyed(&idents, &["icon", "icon"]);
+
+        let result =
+            resolve_collisions(
+                &mut entries,
+                CollisionPolicy::Error,
+                |i| vec![i.to_string()],
+            );
+        assert!(matches!(result, Err(CodegenError::Collisions { .. })));
+    }
+}
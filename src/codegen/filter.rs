@@ -0,0 +1,141 @@
+//! Chooses which assets are included in a codegen output.
+
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{
+    asset_ident::{AssetIdent, AssetKind},
+    config::CodegenConfig,
+};
+
+use super::CodegenError;
+
+pub(super) struct AssetFilter<'a> {
+    include: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+    asset_types: Option<&'a [AssetKind]>,
+}
+
+impl<'a> AssetFilter<'a> {
+    pub fn new(config: &'a CodegenConfig) -> Result<Self, CodegenError> {
+        Ok(Self {
+            include: build_globs(&config.include)?,
+            exclude: build_globs(&config.exclude)?,
+            asset_types: config.asset_types.as_deref(),
+        })
+    }
+
+    pub fn matches(&self, ident: &AssetIdent) -> bool {
+        if let Some(asset_types) = self.asset_types {
+            if !asset_types.contains(&ident.kind()) {
+                return false;
+            }
+        }
+
+        // Globs match an asset if they match its path or any folder it's in
+        let matched = |globs: &Gitignore| {
+            globs
+                .matched_path_or_any_parents(Path::new(ident.as_ref()), false)
+                .is_ignore()
+        };
+
+        // Without any includes, assets that don't match an exclude are kept
+        if self.include.as_ref().is_some_and(|globs| !matched(globs)) {
+            return false;
+        }
+        !self.exclude.as_ref().is_some_and(matched)
+    }
+}
+
+fn build_globs(globs: &[String]) -> Result<Option<Gitignore>, CodegenError> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+
+    // Asset idents are already relative to the project root
+    let mut builder = GitignoreBuilder::new("");
+    for glob in globs {
+        builder.add_line(None, glob)?;
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::{config, ident};
+
+    fn filtered(options: &str, paths: &[&str]) -> Vec<String> {
+        let config = config(&("format = \"json\"\n".to_string() + options));
+        let filter = AssetFilter::new(&config).unwrap();
+
+        paths
+            .iter()
+            .map(|p| ident(p))
+            .filter(|i| filter.matches(i))
+            .map(|i| i.to_string())
+            .collect()
+    }
+
+    const PATHS: &[&str] = &[
+        "assets/ui/close.png",
+        "assets/ui/click.ogg",
+        "assets/server/secret.png",
+        "assets/tree.fbx",
+    ];
+
+    #[test]
+    fn include_exclude() {
+        assert_eq!(
+            filtered("include = [\"assets/ui/**\"]", PATHS),
+            vec!["assets/ui/close.png", "assets/ui/click.ogg"]
+        );
+        assert_eq!(
+            filtered("exclude = [\"**/server/**\"]", PATHS),
+            vec![
+                "assets/ui/close.png",
+                "assets/ui/click.ogg",
+                "assets/tree.fbx"
+            ]
+        );
+        assert_eq!(
+            filtered(
+                "include = [\"*.png\"]\nexclude = [\"assets/server/**\"]",
+                PATHS
+            ),
+            vec!["assets/ui/close.png"]
+        );
+    }
+
+    #[test]
+    fn folders() {
+        assert_eq!(
+            filtered("exclude = [\"assets/server\"]", PATHS),
+            vec![
+                "assets/ui/close.png",
+                "assets/ui/click.ogg",
+                "assets/tree.fbx"
+            ]
+        );
+        assert_eq!(
+            filtered("include = [\"assets/ui\"]", PATHS),
+            vec!["assets/ui/close.png", "assets/ui/click.ogg"]
+        );
+    }
+
+    #[test]
+    fn asset_types() {
+        assert_eq!(
+            filtered("asset_types = [\"audio\", \"model\"]", PATHS),
+            vec!["assets/ui/click.ogg", "assets/tree.fbx"]
+        );
+        assert_eq!(
+            filtered(
+                "asset_types = [\"image\"]\nexclude = [\"secret.png\"]",
+                PATHS
+            ),
+            vec!["assets/ui/close.png"]
+        );
+    }
+}
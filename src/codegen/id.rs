@@ -0,0 +1,134 @@
+//! Formats asset IDs according to a codegen output's `id_format`.
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::asset_ident::AssetIdent;
+
+use super::CodegenError;
+
+/// How asset IDs are written in a codegen output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdFormat {
+    /// The full asset string, e.g. `"rbxassetid://123"`.
+    #[default]
+    Uri,
+
+    /// The numeric asset ID, e.g. `123`.
+    Number,
+
+    /// (Luau and TypeScript only) A `Content` value, e.g. `Content.fromAssetId(123)`.
+    Content,
+}
+
+impl IdFormat {
+    /// The type of IDs in this format in Luau and TypeScript.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            IdFormat::Uri => "string",
+            IdFormat::Number => "number",
+            IdFormat::Content => "Content",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum AssetId {
+    Uri(String),
+    Number(u64),
+    Content { uri: String, number: Option<u64> },
+}
+
+impl AssetId {
+    pub fn new(ident: &AssetIdent, uri: &str, format: IdFormat) -> Result<Self, CodegenError> {
+        let number = parse_number(uri);
+
+        match format {
+            IdFormat::Uri => Ok(AssetId::Uri(uri.to_string())),
+            IdFormat::Number => {
+                number
+                    .map(AssetId::Number)
+                    .ok_or_else(|| CodegenError::NonNumericId {
+                        ident: ident.clone(),
+                        id: uri.to_string(),
+                    })
+            }
+            // Local assets don't have numeric IDs but can still be loaded by their URI
+            IdFormat::Content => Ok(AssetId::Content {
+                uri: uri.to_string(),
+                number,
+            }),
+        }
+    }
+
+    pub fn format(&self) -> IdFormat {
+        match self {
+            AssetId::Uri(_) => IdFormat::Uri,
+            AssetId::Number(_) => IdFormat::Number,
+            AssetId::Content { .. } => IdFormat::Content,
+        }
+    }
+
+    /// Formats the ID as a Luau or TypeScript expression, quoting URIs with `format_string`.
+    pub fn to_expression(&self, format_string: fn(&str) -> String) -> String {
+        match self {
+            AssetId::Uri(uri) => format_string(uri),
+            AssetId::Number(n) => n.to_string(),
+            AssetId::Content {
+                number: Some(n), ..
+            } => format!("Content.fromAssetId({n})"),
+            AssetId::Content { uri, number: None } => {
+                format!("Content.fromUri({})", format_string(uri))
+            }
+        }
+    }
+}
+
+impl Serialize for AssetId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AssetId::Uri(uri) | AssetId::Content { uri, .. } => serializer.serialize_str(uri),
+            AssetId::Number(n) => serializer.serialize_u64(*n),
+        }
+    }
+}
+
+// Returns the number in an `rbxassetid://` URI
+fn parse_number(uri: &str) -> Option<u64> {
+    uri.strip_prefix("rbxassetid://")?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::ident;
+
+    fn expression(uri: &str, format: IdFormat) -> Result<String, CodegenError> {
+        let id = AssetId::new(&ident("a.png"), uri, format)?;
+        Ok(id.to_expression(|s| format!("{s:?}")))
+    }
+
+    #[test]
+    fn formats() {
+        let remote = "rbxassetid://123";
+        let local = "rbxasset://.runway/a.png";
+
+        assert_eq!(
+            expression(remote, IdFormat::Uri).unwrap(),
+            "\"rbxassetid://123\""
+        );
+        assert_eq!(expression(remote, IdFormat::Number).unwrap(), "123");
+        assert_eq!(
+            expression(remote, IdFormat::Content).unwrap(),
+            "Content.fromAssetId(123)"
+        );
+        assert_eq!(
+            expression(local, IdFormat::Content).unwrap(),
+            "Content.fromUri(\"rbxasset://.runway/a.png\")"
+        );
+        assert!(matches!(
+            expression(local, IdFormat::Number),
+            Err(CodegenError::NonNumericId { .. })
+        ));
+    }
+}
@@ -1,40 +1,41 @@
-use super::{CodegenError, Object, Value};
+use super::{CodegenError, Value};
 
 pub(super) fn generate_json(tree: &Value) -> Result<String, CodegenError> {
-    let Value::Object(root) = tree else { panic!() };
-    Ok(format_object(root, 0) + "\n")
+    let Value::Object(_) = tree else { panic!() };
+    Ok(format_value(tree, 0) + "\n")
 }
 
-fn format_object(obj: &Object, indent_level: usize) -> String {
+fn format_value(value: &Value, indent_level: usize) -> String {
+    match value {
+        Value::Object(obj) => format_object(obj.0.iter(), indent_level),
+        Value::Record(record) => format_object(record.iter(), indent_level),
+        Value::Id(id) => id.to_expression(|s| format_string(s)),
+        Value::String(s) => format_string(s),
+        Value::Number(n) => n.to_string(),
+        Value::Vector2(x, y) => format!("[{x}, {y}]"),
+    }
+}
+
+fn format_object<'a, I>(entries: I, indent_level: usize) -> String
+where
+    I: Iterator<Item = (&'a String, &'a Value)>,
+{
     let indent = "\t".repeat(indent_level);
     let indent_plus1 = "\t".repeat(indent_level + 1);
 
     let mut s = String::new();
     s.push_str("{\n");
 
-    let mut iter = obj.0.iter().peekable();
+    let mut iter = entries.peekable();
 
     while let Some((k, v)) = iter.next() {
         s.push_str(&(indent_plus1.clone() + &format_string(k) + ": "));
+        s.push_str(&format_value(v, indent_level + 1));
 
-        match v {
-            Value::Object(subobj) => {
-                s.push_str(&format_object(subobj, indent_level + 1));
-
-                if iter.peek().is_some() {
-                    s.push(',');
-                }
-                s.push('\n');
-            }
-            Value::Id(id) => {
-                s.push_str(&format_string(id));
-
-                if iter.peek().is_some() {
-                    s.push(',');
-                }
-                s.push('\n');
-            }
+        if iter.peek().is_some() {
+            s.push(',');
         }
+        s.push('\n');
     }
 
     s.push_str(&(indent + "}"));
@@ -43,5 +44,48 @@ fn format_object(obj: &Object, indent_level: usize) -> String {
 }
 
 fn format_string<S: AsRef<str>>(s: S) -> String {
-    "\"".to_string() + s.as_ref() + "\""
+    let mut out = String::with_capacity(s.as_ref().len() + 2);
+    out.push('"');
+
+    for c in s.as_ref().chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::adversarial_tree;
+
+    #[test]
+    fn escaping() {
+        let expected = r#"{
+	"1st": "rbxassetid://5",
+	"back\\slash": "rbxassetid://2",
+	"end": "rbxassetid://4",
+	"folder name": {
+		"tab\there": "rbxasset://.runway/a\"b\\c.png"
+	},
+	"new\nline": "rbxassetid://3",
+	"say \"hi\"": "rbxassetid://1",
+	"valid_name": "rbxassetid://6",
+	"ünï\u0007": "rbxassetid://7"
+}
+"#;
+
+        assert_eq!(generate_json(&adversarial_tree()).unwrap(), expected);
+    }
 }
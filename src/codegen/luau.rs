@@ -1,42 +1,207 @@
+use crate::config::CodegenConfig;
+
 use super::{CodegenError, Object, Value};
 
 const HEADER_COMMENT: &str =
     "-- This file was @generated by Runway. It is not intended for manual editing.";
 
-pub(super) fn generate_luau(tree: &Value) -> Result<String, CodegenError> {
+// The name of the path lookup function added to the root table
+const GET_HELPER_KEY: &str = "get";
+
+pub(super) fn generate_luau(tree: &Value, config: &CodegenConfig) -> Result<String, CodegenError> {
     let Value::Object(root) = tree else { panic!() };
 
+    if config.get_helper && root.0.contains_key(GET_HELPER_KEY) {
+        return Err(CodegenError::ReservedKey {
+            key: GET_HELPER_KEY.to_string(),
+        });
+    }
+
     let mut s = String::new();
+
+    if config.typed {
+        s.push_str("--!strict\n");
+    }
     s.push_str(HEADER_COMMENT);
-    s.push_str("\nreturn ");
-    s.push_str(&format_object(root, 0));
     s.push('\n');
 
+    if config.get_helper {
+        let leaves = root.leaves();
+
+        if config.typed {
+            s.push_str("\nexport type AssetPath =");
+            if leaves.is_empty() {
+                s.push_str(" never");
+            }
+            for (path, _) in &leaves {
+                s.push_str("\n\t| ");
+                s.push_str(&format_string(path));
+            }
+            s.push_str(&format!(
+                "\n\nlocal paths: {{ [AssetPath]: {} }} = table.freeze({{\n",
+                config.id_format.type_name()
+            ));
+        } else {
+            s.push_str("\nlocal paths = {\n");
+        }
+
+        for (path, value) in &leaves {
+            let id = value.id().ok_or(CodegenError::TreeStructure)?;
+            s.push_str(&("\t[".to_string() + &format_string(path) + "] = "));
+            s.push_str(&id.to_expression(|s| format_string(s)));
+            s.push_str(",\n");
+        }
+
+        if config.typed {
+            s.push_str(&format!(
+                "}})\n\nlocal function get(path: AssetPath): {}\n",
+                config.id_format.type_name()
+            ));
+        } else {
+            s.push_str("}\n\nlocal function get(path)\n");
+        }
+        s.push_str("\treturn paths[path]\nend\n");
+    }
+
+    if config.typed {
+        s.push_str("\nexport type Assets = ");
+        s.push_str(&format_object_type(
+            root.0.iter(),
+            0,
+            config.get_helper.then(|| config.id_format.type_name()),
+        ));
+        s.push_str("\n\nlocal assets: Assets = ");
+        s.push_str(&format_object(root.0.iter(), 0, true, config.get_helper));
+        s.push_str("\n\nreturn assets\n");
+    } else {
+        if config.get_helper {
+            s.push('\n');
+        }
+        s.push_str("return ");
+        s.push_str(&format_object(root.0.iter(), 0, false, config.get_helper));
+        s.push('\n');
+    }
+
     Ok(s)
 }
 
-fn format_object(obj: &Object, indent_level: usize) -> String {
+/// Generates one module of a split output, which lazily requires the modules of its subfolders.
+pub(super) fn generate_luau_module(obj: &Object) -> String {
+    let (folders, leaves): (Vec<_>, Vec<_>) = obj
+        .0
+        .iter()
+        .partition(|(_, v)| matches!(v, Value::Object(_)));
+
+    let mut s = String::new();
+    s.push_str(HEADER_COMMENT);
+    s.push('\n');
+
+    if folders.is_empty() {
+        s.push_str("return ");
+        s.push_str(&format_object(leaves.into_iter(), 0, false, false));
+        s.push('\n');
+        return s;
+    }
+
+    s.push_str("\nlocal children = {\n");
+    for (k, _) in folders {
+        s.push_str(&("\t".to_string() + &format_key(k) + " = true,\n"));
+    }
+    s.push_str("}\n\n");
+
+    // Subfolders are required the first time they're indexed and then cached in the table
+    s.push_str("return setmetatable(");
+    s.push_str(&format_object(leaves.into_iter(), 0, false, false));
+    s.push_str(
+        ", {\n\
+        \t__index = function(self, key)\n\
+        \t\tif children[key] then\n\
+        \t\t\tlocal value = require(script:FindFirstChild(key))\n\
+        \t\t\trawset(self, key, value)\n\
+        \t\t\treturn value\n\
+        \t\tend\n\
+        \t\treturn nil\n\
+        \tend,\n\
+        })\n",
+    );
+
+    s
+}
+
+fn format_value(value: &Value, indent_level: usize, freeze: bool) -> String {
+    match value {
+        Value::Object(obj) => format_object(obj.0.iter(), indent_level, freeze, false),
+        Value::Record(record) => format_object(record.iter(), indent_level, freeze, false),
+        Value::Id(id) => id.to_expression(|s| format_string(s)),
+        Value::String(s) => format_string(s),
+        Value::Number(n) => n.to_string(),
+        Value::Vector2(x, y) => format!("Vector2.new({x}, {y})"),
+    }
+}
+
+fn format_object<'a, I>(entries: I, indent_level: usize, freeze: bool, get_helper: bool) -> String
+where
+    I: Iterator<Item = (&'a String, &'a Value)>,
+{
     let indent = "\t".repeat(indent_level);
     let indent_plus1 = "\t".repeat(indent_level + 1);
 
     let mut s = String::new();
+    if freeze {
+        s.push_str("table.freeze(");
+    }
     s.push_str("{\n");
 
-    let iter = obj.0.iter().peekable();
+    for (k, v) in entries {
+        s.push_str(&(indent_plus1.clone() + &format_key(k) + " = "));
+        s.push_str(&format_value(v, indent_level + 1, freeze));
+        s.push_str(",\n");
+    }
 
-    for (k, v) in iter {
-        s.push_str(&(indent_plus1.clone() + "[" + &format_string(k) + "] = "));
+    if get_helper {
+        s.push_str(&(indent_plus1 + GET_HELPER_KEY + " = get,\n"));
+    }
 
-        match v {
-            Value::Object(subobj) => {
-                s.push_str(&format_object(subobj, indent_level + 1));
-                s.push_str(",\n");
-            }
-            Value::Id(id) => {
-                s.push_str(&format_string(id));
-                s.push_str(",\n");
-            }
-        }
+    s.push_str(&(indent + "}"));
+    if freeze {
+        s.push(')');
+    }
+
+    s
+}
+
+fn format_value_type(value: &Value, indent_level: usize) -> String {
+    match value {
+        Value::Object(obj) => format_object_type(obj.0.iter(), indent_level, None),
+        Value::Record(record) => format_object_type(record.iter(), indent_level, None),
+        Value::Id(id) => id.format().type_name().to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::Vector2(..) => "Vector2".to_string(),
+    }
+}
+
+// `get_helper` is the helper's return type if it should be included
+fn format_object_type<'a, I>(entries: I, indent_level: usize, get_helper: Option<&str>) -> String
+where
+    I: Iterator<Item = (&'a String, &'a Value)>,
+{
+    let indent = "\t".repeat(indent_level);
+    let indent_plus1 = "\t".repeat(indent_level + 1);
+
+    let mut s = String::new();
+    s.push_str("{\n");
+
+    for (k, v) in entries {
+        s.push_str(&(indent_plus1.clone() + &format_key(k) + ": "));
+        s.push_str(&format_value_type(v, indent_level + 1));
+        s.push_str(",\n");
+    }
+
+    if let Some(id_type) = get_helper {
+        s.push_str(&format!(
+            "{indent_plus1}{GET_HELPER_KEY}: (path: AssetPath) -> {id_type},\n"
+        ));
     }
 
     s.push_str(&(indent + "}"));
@@ -44,6 +209,181 @@ fn format_object(obj: &Object, indent_level: usize) -> String {
     s
 }
 
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+fn is_id<S: AsRef<str>>(s: S) -> bool {
+    let s = s.as_ref();
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.contains(&s)
+}
+
+fn format_key<S: AsRef<str>>(s: S) -> String {
+    if is_id(&s) {
+        s.as_ref().to_string()
+    } else {
+        "[".to_string() + &format_string(s) + "]"
+    }
+}
+
 fn format_string<S: AsRef<str>>(s: S) -> String {
-    "\"".to_string() + s.as_ref() + "\""
+    let mut out = String::with_capacity(s.as_ref().len() + 2);
+    out.push('"');
+
+    for c in s.as_ref().chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // Use three digits so a following digit isn't parsed as part of the escape
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::{adversarial_tree, config};
+
+    #[test]
+    fn module() {
+        let Value::Object(root) = adversarial_tree() else {
+            panic!()
+        };
+
+        let expected = r#"-- This file was @generated by Runway. It is not intended for manual editing.
+
+local children = {
+	["folder name"] = true,
+}
+
+return setmetatable({
+	["1st"] = "rbxassetid://5",
+	["back\\slash"] = "rbxassetid://2",
+	["end"] = "rbxassetid://4",
+	["new\nline"] = "rbxassetid://3",
+	["say \"hi\""] = "rbxassetid://1",
+	valid_name = "rbxassetid://6",
+	["ünï\007"] = "rbxassetid://7",
+}, {
+	__index = function(self, key)
+		if children[key] then
+			local value = require(script:FindFirstChild(key))
+			rawset(self, key, value)
+			return value
+		end
+		return nil
+	end,
+})
+"#;
+
+        assert_eq!(generate_luau_module(&root), expected);
+    }
+
+    #[test]
+    fn escaping() {
+        let expected = r#"-- This file was @generated by Runway. It is not intended for manual editing.
+return {
+	["1st"] = "rbxassetid://5",
+	["back\\slash"] = "rbxassetid://2",
+	["end"] = "rbxassetid://4",
+	["folder name"] = {
+		["tab\there"] = "rbxasset://.runway/a\"b\\c.png",
+	},
+	["new\nline"] = "rbxassetid://3",
+	["say \"hi\""] = "rbxassetid://1",
+	valid_name = "rbxassetid://6",
+	["ünï\007"] = "rbxassetid://7",
+}
+"#;
+
+        let config = config("format = \"luau\"");
+        assert_eq!(
+            generate_luau(&adversarial_tree(), &config).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn typed() {
+        let expected = r#"--!strict
+-- This file was @generated by Runway. It is not intended for manual editing.
+
+export type AssetPath =
+	| "1st"
+	| "back\\slash"
+	| "end"
+	| "folder name/tab\there"
+	| "new\nline"
+	| "say \"hi\""
+	| "valid_name"
+	| "ünï\007"
+
+local paths: { [AssetPath]: string } = table.freeze({
+	["1st"] = "rbxassetid://5",
+	["back\\slash"] = "rbxassetid://2",
+	["end"] = "rbxassetid://4",
+	["folder name/tab\there"] = "rbxasset://.runway/a\"b\\c.png",
+	["new\nline"] = "rbxassetid://3",
+	["say \"hi\""] = "rbxassetid://1",
+	["valid_name"] = "rbxassetid://6",
+	["ünï\007"] = "rbxassetid://7",
+})
+
+local function get(path: AssetPath): string
+	return paths[path]
+end
+
+export type Assets = {
+	["1st"]: string,
+	["back\\slash"]: string,
+	["end"]: string,
+	["folder name"]: {
+		["tab\there"]: string,
+	},
+	["new\nline"]: string,
+	["say \"hi\""]: string,
+	valid_name: string,
+	["ünï\007"]: string,
+	get: (path: AssetPath) -> string,
+}
+
+local assets: Assets = table.freeze({
+	["1st"] = "rbxassetid://5",
+	["back\\slash"] = "rbxassetid://2",
+	["end"] = "rbxassetid://4",
+	["folder name"] = table.freeze({
+		["tab\there"] = "rbxasset://.runway/a\"b\\c.png",
+	}),
+	["new\nline"] = "rbxassetid://3",
+	["say \"hi\""] = "rbxassetid://1",
+	valid_name = "rbxassetid://6",
+	["ünï\007"] = "rbxassetid://7",
+	get = get,
+})
+
+return assets
+"#;
+
+        let config = config("format = \"luau\"\ntyped = true\nget_helper = true");
+        assert_eq!(
+            generate_luau(&adversarial_tree(), &config).unwrap(),
+            expected
+        );
+    }
 }
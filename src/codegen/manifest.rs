@@ -0,0 +1,249 @@
+//! Generates a JSON manifest of every asset and target for other tools to read.
+//!
+//! The schema is versioned by [`MANIFEST_VERSION`], which is increased whenever
+//! a field is removed or changes meaning. Fields may be added without a new version.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+
+use crate::{
+    asset_ident::{AssetIdent, AssetKind},
+    config::{CodegenConfig, Config},
+    state::{Sprite, State},
+};
+
+use super::{filter::AssetFilter, CodegenError};
+
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    version: u32,
+    runway_version: &'a str,
+    project: &'a str,
+    assets: Vec<ManifestAsset<'a>>,
+}
+
+#[derive(Serialize)]
+struct ManifestAsset<'a> {
+    ident: &'a AssetIdent,
+    #[serde(rename = "type")]
+    kind: AssetKind,
+
+    // Measured when syncing, or null if the asset hasn't been measured
+    hash: Option<&'a str>,
+    size: Option<u64>,
+    width: Option<u32>,
+    height: Option<u32>,
+    duration: Option<f64>,
+
+    // Only present for images shrunk to fit their input's `max_size` or trimmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale: Option<f64>,
+
+    // Only present for trimmed images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trim_x: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trim_y: Option<u32>,
+
+    // Only present for images from inputs with `dpi_scales`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dpi_scale: Option<u32>,
+
+    targets: BTreeMap<&'a str, ManifestTarget<'a>>,
+}
+
+#[derive(Serialize)]
+struct ManifestTarget<'a> {
+    id: &'a str,
+    hash: &'a str,
+    synced_at: Option<u64>,
+
+    // Only present for images packed into a spritesheet, where `id` is the spritesheet's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sprite: Option<Sprite>,
+}
+
+pub(super) fn generate_manifest(
+    project: &Config,
+    state: &State,
+    config: &CodegenConfig,
+) -> Result<String, CodegenError> {
+    let filter = AssetFilter::new(config)?;
+
+    let assets = state
+        .assets
+        .iter()
+        .filter(|(ident, _)| filter.matches(ident))
+        .map(|(ident, asset)| {
+            let metadata = asset.metadata.as_ref();
+
+            ManifestAsset {
+                ident,
+                kind: ident.kind(),
+                hash: metadata.map(|m| m.hash.as_str()),
+                size: metadata.map(|m| m.size),
+                width: metadata.and_then(|m| m.width),
+                height: metadata.and_then(|m| m.height),
+                duration: metadata.and_then(|m| m.duration),
+                original_width: metadata.and_then(|m| m.original_width),
+                original_height: metadata.and_then(|m| m.original_height),
+                scale: metadata.and_then(|m| m.scale),
+                trim_x: metadata.and_then(|m| m.trim_x),
+                trim_y: metadata.and_then(|m| m.trim_y),
+                dpi_scale: asset.dpi_scale,
+                targets: asset
+                    .targets
+                    .iter()
+                    .map(|(key, target)| {
+                        (
+                            key.as_str(),
+                            ManifestTarget {
+                                id: &target.id,
+                                hash: &target.hash,
+                                synced_at: target.synced_at,
+                                sprite: target.sprite,
+                            },
+                        )
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        runway_version: env!("CARGO_PKG_VERSION"),
+        project: &project.name,
+        assets,
+    };
+
+    let mut out = Vec::new();
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(b"\t"));
+    manifest
+        .serialize(&mut serializer)
+        .map_err(|_| CodegenError::TreeStructure)?;
+
+    Ok(String::from_utf8(out).unwrap() + "\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        codegen::test::{config, ident, state},
+        state::{AssetMetadata, Sprite},
+    };
+
+    #[test]
+    fn manifest() {
+        let metadata = AssetMetadata {
+            hash: "abc".to_string(),
+            size: 1024,
+            width: Some(64),
+            height: Some(32),
+            duration: None,
+            ..Default::default()
+        };
+        let mut state = state(&[("ui/close.png", Some(metadata)), ("click.ogg", None)]);
+        state
+            .assets
+            .get_mut(&ident("ui/close.png"))
+            .unwrap()
+            .targets
+            .get_mut("local")
+            .unwrap()
+            .synced_at = Some(1700000000);
+
+        let project: Config = toml::from_str("name = \"my-project\"").unwrap();
+        let config = config("format = \"manifest\"");
+
+        let expected = format!(
+            r#"{{
+	"version": 1,
+	"runway_version": "{}",
+	"project": "my-project",
+	"assets": [
+		{{
+			"ident": "click.ogg",
+			"type": "audio",
+			"hash": null,
+			"size": null,
+			"width": null,
+			"height": null,
+			"duration": null,
+			"targets": {{
+				"local": {{
+					"id": "rbxasset://.runway/click.ogg",
+					"hash": "hash",
+					"synced_at": null
+				}}
+			}}
+		}},
+		{{
+			"ident": "ui/close.png",
+			"type": "image",
+			"hash": "abc",
+			"size": 1024,
+			"width": 64,
+			"height": 32,
+			"duration": null,
+			"targets": {{
+				"local": {{
+					"id": "rbxasset://.runway/ui/close.png",
+					"hash": "hash",
+					"synced_at": 1700000000
+				}}
+			}}
+		}}
+	]
+}}
+"#,
+            env!("CARGO_PKG_VERSION")
+        );
+
+        assert_eq!(
+            generate_manifest(&project, &state, &config).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn sprites() {
+        let mut state = state(&[("icons/close.png", None)]);
+        let target = state
+            .assets
+            .get_mut(&ident("icons/close.png"))
+            .unwrap()
+            .targets
+            .get_mut("local")
+            .unwrap();
+        target.id = "rbxasset://.runway/spritesheets/abc.png".to_string();
+        target.sprite = Some(Sprite {
+            x: 34,
+            y: 0,
+            width: 32,
+            height: 16,
+        });
+
+        let project: Config = toml::from_str("name = \"my-project\"").unwrap();
+        let config = config("format = \"manifest\"");
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&generate_manifest(&project, &state, &config).unwrap()).unwrap();
+        let target = &manifest["assets"][0]["targets"]["local"];
+        assert_eq!(target["id"], "rbxasset://.runway/spritesheets/abc.png");
+        assert_eq!(
+            target["sprite"],
+            serde_json::json!({ "x": 34, "y": 0, "width": 32, "height": 16 })
+        );
+    }
+}
@@ -1,33 +1,143 @@
-use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
 use thiserror::Error;
 
 use crate::{
     asset_ident::AssetIdent,
     codegen::typescript::generate_typescript_declaration,
     config::{CodegenConfig, Config, ConfigError, TargetConfig},
-    state::{State, StateError},
+    dpi::split_scale,
+    state::{AssetMetadata, AssetState, Sprite, State, StateError},
 };
 
+use self::case::transform_segment;
+use self::collision::{resolve_collisions, KeyCollision, KeyedAsset};
+use self::filter::AssetFilter;
+use self::id::AssetId;
 use self::json::generate_json;
-use self::luau::generate_luau;
-use self::typescript::generate_typescript;
+use self::luau::{generate_luau, generate_luau_module};
+use self::manifest::generate_manifest;
+use self::model::{generate_model, generate_model_tree};
+use self::template::generate_template;
+use self::typescript::{generate_typescript, generate_typescript_module};
 
+mod case;
+mod collision;
+mod filter;
+mod id;
 mod json;
 mod luau;
+mod manifest;
+mod model;
+mod template;
 mod typescript;
 
+pub use self::case::KeyCase;
+pub use self::collision::CollisionPolicy;
+pub use self::id::IdFormat;
+pub use self::model::ImageClass;
+
 #[derive(Debug, Clone)]
 enum Value {
     Object(Object),
-    Id(String),
+    Record(Record),
+    Id(AssetId),
+    String(String),
+    Number(f64),
+    Vector2(u32, u32),
+}
+
+impl Value {
+    // Returns the asset ID of a leaf value
+    fn id(&self) -> Option<&AssetId> {
+        match self {
+            Value::Id(id) => Some(id),
+            Value::Record(record) => record.iter().find_map(|(k, v)| match v {
+                Value::Id(id) if k == "id" => Some(id),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Object(obj) => serializer.collect_map(obj.0.iter()),
+            Value::Record(record) => serializer.collect_map(record.iter()),
+            Value::Id(id) => id.serialize(serializer),
+            Value::String(s) => serializer.serialize_str(s),
+            // Keep whole numbers as integers so they aren't written as `64.0`
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 2_f64.powi(53) => {
+                serializer.serialize_i64(*n as i64)
+            }
+            Value::Number(n) => serializer.serialize_f64(*n),
+            Value::Vector2(x, y) => [x, y].serialize(serializer),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
-struct Object(HashMap<String, Value>);
+struct Object(BTreeMap<String, Value>);
+
+// An asset's ID along with other fields describing it, in the order they should be generated
+#[derive(Debug, Clone, Default)]
+struct Record(Vec<(String, Value)>);
+
+impl Record {
+    fn push<K: Into<String>>(&mut self, key: K, value: Value) {
+        self.0.push((key.into(), value));
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+}
 
-fn transform_ident(ident: &AssetIdent, config: &CodegenConfig) -> String {
+impl Object {
+    // Returns every non-object value in this object with the keys leading to it joined by `/`
+    fn leaves(&self) -> Vec<(String, &Value)> {
+        let mut leaves = Vec::new();
+
+        for (k, v) in &self.0 {
+            match v {
+                Value::Object(subobj) => {
+                    for (path, leaf) in subobj.leaves() {
+                        leaves.push((k.clone() + "/" + &path, leaf));
+                    }
+                }
+                _ => leaves.push((k.clone(), v)),
+            }
+        }
+
+        leaves
+    }
+
+    // Returns this object and every object nested in it with the keys leading to each
+    fn folders(&self) -> Vec<(Vec<&String>, &Object)> {
+        let mut folders = vec![(Vec::new(), self)];
+
+        for (k, v) in &self.0 {
+            if let Value::Object(subobj) = v {
+                for (mut keys, folder) in subobj.folders() {
+                    keys.insert(0, k);
+                    folders.push((keys, folder));
+                }
+            }
+        }
+
+        folders
+    }
+}
+
+fn transform_ident(ident: &AssetIdent, config: &CodegenConfig, strip_extension: bool) -> String {
     let mut path = PathBuf::from_str(ident.as_ref()).unwrap();
     path = if let Some(prefix) = &config.strip_prefix {
         if let Ok(t) = path.strip_prefix(prefix) {
@@ -43,10 +153,54 @@ fn transform_ident(ident: &AssetIdent, config: &CodegenConfig) -> String {
     } else {
         path
     };
-    if config.strip_extension {
+    if strip_extension {
         path.set_extension("");
     }
-    path.to_string_lossy().to_string()
+
+    let path = path.to_string_lossy();
+    let segments = path.split('/').collect::<Vec<_>>();
+    let last = segments.len() - 1;
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            // A kept extension isn't part of the name, so it isn't transformed
+            let dot = segment.rfind('.').filter(|&dot| dot > 0);
+            match dot.filter(|_| i == last && !strip_extension) {
+                Some(dot) => transform_segment(&segment[..dot], config) + &segment[dot..],
+                None => transform_segment(segment, config),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Returns the path of keys an asset is generated at.
+// DPI variants are grouped under their base name with a key for each density, e.g. `icon/2x`.
+fn asset_key(
+    ident: &AssetIdent,
+    dpi_scale: Option<u32>,
+    config: &CodegenConfig,
+    strip_extension: bool,
+) -> Vec<String> {
+    let (ident, scale) = match dpi_scale {
+        Some(scale) => (split_scale(ident).0, Some(scale)),
+        None => (ident.clone(), None),
+    };
+
+    let ident_string = transform_ident(&ident, config, strip_extension);
+
+    let mut key = if config.flatten {
+        vec![ident_string]
+    } else {
+        ident_string.split('/').map(str::to_string).collect()
+    };
+
+    if let Some(scale) = scale {
+        key.push(format!("{scale}x"));
+    }
+
+    key
 }
 
 fn generate_tree(
@@ -54,53 +208,171 @@ fn generate_tree(
     config: &CodegenConfig,
     target: &TargetConfig,
 ) -> Result<Value, CodegenError> {
-    let mut root = Value::Object(Object::default());
+    generate_tree_with(state, config, target, |_, value| value)
+}
+
+// Generates the tree for an output, letting it reshape the value of each asset
+fn generate_tree_with(
+    state: &State,
+    config: &CodegenConfig,
+    target: &TargetConfig,
+    shape: impl Fn(&AssetIdent, Value) -> Value,
+) -> Result<Value, CodegenError> {
+    let filter = AssetFilter::new(config)?;
+    let mut entries = Vec::new();
+    let mut ids = HashMap::new();
 
     for (ident, asset) in &state.assets {
-        let target_state =
-            asset
-                .targets
-                .get(&target.key)
-                .ok_or_else(|| CodegenError::MissingAsset {
-                    ident: ident.clone(),
-                })?;
+        if !filter.matches(ident) {
+            continue;
+        }
 
-        let mut head = &mut root;
+        let Some(id) = asset_id(ident, asset, config, target)? else {
+            continue;
+        };
+        ids.insert(ident, id);
 
-        let ident_string = transform_ident(ident, config);
+        entries.push(KeyedAsset {
+            ident,
+            key: asset_key(ident, asset.dpi_scale, config, config.strip_extension),
+        });
+    }
 
-        let key = if config.flatten {
-            ident_string
-        } else {
-            let mut parts = ident_string.split('/').collect::<Vec<_>>();
-            let last_part = parts.pop().ok_or_else(|| CodegenError::TreeStructure)?;
-
-            for part in parts {
-                match head {
-                    Value::Object(obj) => {
-                        if !obj.0.contains_key(part) {
-                            obj.0
-                                .insert(part.to_string(), Value::Object(Object::default()));
-                        }
-
-                        head = obj.0.get_mut(part).unwrap();
-                    }
-                    Value::Id(_) => return Err(CodegenError::TreeStructure),
-                }
-            }
+    resolve_collisions(&mut entries, config.on_collision, |ident| {
+        asset_key(ident, state.assets[ident].dpi_scale, config, false)
+    })?;
+
+    let mut root = Object::default();
+
+    for entry in entries {
+        let asset = &state.assets[entry.ident];
+        let (id, sprite) = ids[entry.ident];
+        let id = AssetId::new(entry.ident, id, config.id_format)?;
+
+        let value = if config.metadata {
+            let metadata =
+                asset
+                    .metadata
+                    .as_ref()
+                    .ok_or_else(|| CodegenError::MissingMetadata {
+                        ident: entry.ident.clone(),
+                    })?;
 
-            last_part.into()
+            let mut record = asset_record(entry.ident, id, metadata);
+            push_sprite(&mut record, sprite);
+            Value::Record(record)
+        } else if sprite.is_some() {
+            let mut record = Record::default();
+            record.push("id", Value::Id(id));
+            push_sprite(&mut record, sprite);
+            Value::Record(record)
+        } else {
+            Value::Id(id)
         };
+        let value = shape(entry.ident, value);
+
+        let (last_part, parts) = entry.key.split_last().ok_or(CodegenError::TreeStructure)?;
 
-        match head {
-            Value::Object(obj) => obj
+        let mut head = &mut root;
+        for part in parts {
+            let next = head
                 .0
-                .insert(key.to_string(), Value::Id(target_state.id.clone())),
-            Value::Id(_) => return Err(CodegenError::TreeStructure),
-        };
+                .entry(part.clone())
+                .or_insert_with(|| Value::Object(Object::default()));
+
+            head = match next {
+                Value::Object(obj) => obj,
+                _ => return Err(CodegenError::TreeStructure),
+            };
+        }
+
+        head.0.insert(last_part.clone(), value);
     }
 
-    Ok(root)
+    Ok(Value::Object(root))
+}
+
+// Returns the ID to generate for an asset and its area if it's in a spritesheet,
+// or `None` if it should be left out
+fn asset_id<'a>(
+    ident: &AssetIdent,
+    asset: &'a AssetState,
+    config: &'a CodegenConfig,
+    target: &TargetConfig,
+) -> Result<Option<(&'a str, Option<&'a Sprite>)>, CodegenError> {
+    if let Some(target_state) = asset.targets.get(&target.key) {
+        return Ok(Some((&target_state.id, target_state.sprite.as_ref())));
+    }
+
+    let missing = || CodegenError::MissingAsset {
+        ident: ident.clone(),
+    };
+
+    match config.missing {
+        MissingPolicy::Error => Err(missing()),
+        MissingPolicy::Skip => {
+            log::debug!(
+                "Skipping '{}' which has not been uploaded for target '{}'",
+                ident,
+                target.key
+            );
+            Ok(None)
+        }
+        MissingPolicy::Placeholder => {
+            log::warn!(
+                "Using placeholder for '{}' which has not been uploaded for target '{}'",
+                ident,
+                target.key
+            );
+            // Checked when reading the config
+            Ok(config.placeholder.as_deref().map(|id| (id, None)))
+        }
+        MissingPolicy::Fallback => {
+            // Checked when reading the config
+            let fallback = config.fallback.as_ref().unwrap();
+
+            let target_state = asset.targets.get(fallback).ok_or_else(missing)?;
+            Ok(Some((&target_state.id, target_state.sprite.as_ref())))
+        }
+    }
+}
+
+fn asset_record(ident: &AssetIdent, id: AssetId, metadata: &AssetMetadata) -> Record {
+    let mut record = Record::default();
+
+    record.push("id", Value::Id(id));
+    if let Some(width) = metadata.width {
+        record.push("width", Value::Number(width.into()));
+    }
+    if let Some(height) = metadata.height {
+        record.push("height", Value::Number(height.into()));
+    }
+    record.push("size", Value::Number(metadata.size as f64));
+    record.push("hash", Value::String(metadata.hash.clone()));
+    record.push("type", Value::String(ident.kind().to_string()));
+    if let Some(duration) = metadata.duration {
+        record.push("duration", Value::Number(duration));
+    }
+    if let (Some(width), Some(height)) = (metadata.original_width, metadata.original_height) {
+        record.push("originalWidth", Value::Number(width.into()));
+        record.push("originalHeight", Value::Number(height.into()));
+    }
+    if let Some(scale) = metadata.scale {
+        record.push("scale", Value::Number(scale));
+    }
+    if let (Some(x), Some(y)) = (metadata.trim_x, metadata.trim_y) {
+        record.push("trimOffset", Value::Vector2(x, y));
+    }
+
+    record
+}
+
+// Adds the area of a packed image within its spritesheet, as used by `ImageRectOffset` and `ImageRectSize`
+fn push_sprite(record: &mut Record, sprite: Option<&Sprite>) {
+    if let Some(sprite) = sprite {
+        record.push("imageRectOffset", Value::Vector2(sprite.x, sprite.y));
+        record.push("imageRectSize", Value::Vector2(sprite.width, sprite.height));
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -113,6 +385,28 @@ pub enum CodegenFormat {
     Typescript,
     #[serde(alias = "d.ts")]
     TypescriptDeclaration,
+    Template,
+    #[serde(alias = "model.json")]
+    Model,
+    Manifest,
+}
+
+/// What to do when an asset hasn't been uploaded for the target being generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPolicy {
+    /// Fail to generate the output.
+    #[default]
+    Error,
+
+    /// Leave the asset out of the output.
+    Skip,
+
+    /// Use the output's `placeholder` ID.
+    Placeholder,
+
+    /// Use the asset's ID from the output's `fallback` target.
+    Fallback,
 }
 
 pub fn generate_all(
@@ -125,7 +419,7 @@ pub fn generate_all(
     log::info!("Generating {} outputs", config.codegens.len());
 
     for codegen in &config.codegens {
-        match generate(state, codegen, target) {
+        match generate(config, state, codegen, target) {
             Ok(_) => {}
             Err(e) => {
                 log::error!("{}", e);
@@ -145,6 +439,7 @@ pub fn generate_all(
 }
 
 fn generate(
+    project: &Config,
     state: &State,
     config: &CodegenConfig,
     target: &TargetConfig,
@@ -155,19 +450,230 @@ fn generate(
         config.path.display()
     );
 
-    let tree = generate_tree(state, config, target)?;
+    let files = render(project, state, config, target)?;
+
+    for (path, contents) in &files {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)?;
+    }
+
+    for path in stale_files(config, &files) {
+        log::debug!("Removing stale module {}", path.display());
+        fs::remove_file(&path)?;
+
+        // Remove folders left empty, stopping at the first one that isn't
+        for dir in path.ancestors().skip(1) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the path and contents of each file in a codegen output.
+pub fn render(
+    project: &Config,
+    state: &State,
+    config: &CodegenConfig,
+    target: &TargetConfig,
+) -> Result<Vec<(PathBuf, String)>, CodegenError> {
+    // Manifests always include every target
+    if let CodegenFormat::Manifest = config.format {
+        let contents = generate_manifest(project, state, config)?;
+        return Ok(vec![(config.path.clone(), contents)]);
+    }
+
+    let targets = codegen_targets(project, config, target)?;
+
+    if config.path_has_target() {
+        // Write a separate file for each target
+        let mut files = Vec::new();
+        for t in targets {
+            let path = config.path_for_target(&t.key);
+            files.extend(render_contents(project, state, config, &[t], false, path)?);
+        }
+        Ok(files)
+    } else {
+        let keyed = config.targets.is_some();
+        render_contents(project, state, config, &targets, keyed, config.path.clone())
+    }
+}
+
+// Returns the targets an output should be generated for, defaulting to the synced target
+fn codegen_targets<'a>(
+    project: &'a Config,
+    config: &CodegenConfig,
+    target: &'a TargetConfig,
+) -> Result<Vec<&'a TargetConfig>, CodegenError> {
+    let keys = match (&config.targets, &config.target) {
+        (Some(keys), _) => keys.clone(),
+        (None, Some(key)) => vec![key.clone()],
+        (None, None) => return Ok(vec![target]),
+    };
+
+    keys.iter()
+        .map(|key| {
+            project
+                .targets
+                .iter()
+                .find(|t| t.key == *key)
+                .ok_or_else(|| ConfigError::UnknownCodegenTarget { key: key.clone() }.into())
+        })
+        .collect()
+}
+
+// Generates an output's contents. If `keyed` is true, the tree for each target
+// is nested under the target's key.
+fn render_contents(
+    project: &Config,
+    state: &State,
+    config: &CodegenConfig,
+    targets: &[&TargetConfig],
+    keyed: bool,
+    path: PathBuf,
+) -> Result<Vec<(PathBuf, String)>, CodegenError> {
+    let trees = targets
+        .iter()
+        .map(|t| {
+            let tree = match config.format {
+                CodegenFormat::Model => generate_model_tree(state, config, t)?,
+                _ => generate_tree(state, config, t)?,
+            };
+            Ok((*t, tree))
+        })
+        .collect::<Result<Vec<_>, CodegenError>>()?;
+
+    let tree = if keyed {
+        Value::Object(Object(
+            trees
+                .iter()
+                .map(|(t, tree)| (t.key.clone(), tree.clone()))
+                .collect(),
+        ))
+    } else {
+        trees[0].1.clone()
+    };
+
+    if config.split {
+        return render_split(&tree, config, path);
+    }
 
     let contents = match config.format {
         CodegenFormat::Json => generate_json(&tree),
-        CodegenFormat::Luau => generate_luau(&tree),
+        CodegenFormat::Luau => generate_luau(&tree, config),
         CodegenFormat::Typescript => generate_typescript(&tree),
         CodegenFormat::TypescriptDeclaration => generate_typescript_declaration(&tree),
+        CodegenFormat::Template => generate_template(&tree, &trees, config, project),
+        CodegenFormat::Model => generate_model(&tree, config),
+        CodegenFormat::Manifest => unreachable!(),
     }?;
 
-    fs::create_dir_all(config.path.parent().unwrap())?;
-    fs::write(&config.path, contents)?;
+    Ok(vec![(path, contents)])
+}
 
-    Ok(())
+// Renders a module for each folder in the tree, in a directory at `path`
+fn render_split(
+    tree: &Value,
+    config: &CodegenConfig,
+    path: PathBuf,
+) -> Result<Vec<(PathBuf, String)>, CodegenError> {
+    let Value::Object(root) = tree else { panic!() };
+
+    let mut files = Vec::new();
+
+    for (keys, folder) in root.folders() {
+        let mut folder_path = path.clone();
+        for key in keys {
+            if !is_valid_folder_name(key) {
+                return Err(CodegenError::InvalidFolderName { key: key.clone() });
+            }
+            folder_path.push(key);
+        }
+
+        // Checked when reading the config
+        let contents = match config.format {
+            CodegenFormat::Luau => generate_luau_module(folder),
+            CodegenFormat::Typescript => generate_typescript_module(folder),
+            _ => unreachable!(),
+        };
+
+        let file_name = split_file_name(&config.format).unwrap();
+        files.push((folder_path.join(file_name), contents));
+    }
+
+    Ok(files)
+}
+
+fn split_file_name(format: &CodegenFormat) -> Option<&'static str> {
+    match format {
+        CodegenFormat::Luau => Some("init.lua"),
+        CodegenFormat::Typescript => Some("index.ts"),
+        _ => None,
+    }
+}
+
+/// Returns modules on disk from an earlier run of a split output that it no longer generates,
+/// such as the modules of folders that were removed or renamed.
+pub fn stale_files(config: &CodegenConfig, files: &[(PathBuf, String)]) -> Vec<PathBuf> {
+    let Some(file_name) = split_file_name(&config.format).filter(|_| config.split) else {
+        return Vec::new();
+    };
+
+    let expected = files
+        .iter()
+        .map(|(path, _)| path.as_path())
+        .collect::<HashSet<_>>();
+    let dirs = expected
+        .iter()
+        .filter_map(|path| path.parent())
+        .collect::<HashSet<_>>();
+
+    let mut stale = Vec::new();
+    for dir in &dirs {
+        // Search from the top of each output directory
+        if !dir.ancestors().skip(1).any(|a| dirs.contains(a)) {
+            find_generated_modules(dir, file_name, &mut stale);
+        }
+    }
+
+    stale.retain(|path| !expected.contains(path.as_path()));
+    stale.sort();
+    stale
+}
+
+// Finds modules with a generated header in a directory and its subdirectories
+fn find_generated_modules(dir: &Path, file_name: &str, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            find_generated_modules(&path, file_name, found);
+        } else if entry.file_name() == file_name
+            && fs::read_to_string(&path).is_ok_and(|s| {
+                s.lines()
+                    .next()
+                    .is_some_and(|l| l.contains("@generated by Runway"))
+            })
+        {
+            found.push(path);
+        }
+    }
+}
+
+// Folders are named after their key, so keys must be valid file names on every platform
+fn is_valid_folder_name(key: &str) -> bool {
+    !key.is_empty()
+        && key != "."
+        && key != ".."
+        && !key.ends_with(['.', ' '])
+        && !key.chars().any(|c| {
+            c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        })
 }
 
 #[derive(Debug, Error)]
@@ -178,9 +684,39 @@ pub enum CodegenError {
     #[error("Asset '{}' has not been uploaded for the codegen target", .ident)]
     MissingAsset { ident: AssetIdent },
 
+    #[error(
+        "Some assets map to the same key:\n{}",
+        .collisions.iter().map(|c| format!("  {c}")).collect::<Vec<_>>().join("\n")
+    )]
+    Collisions { collisions: Vec<KeyCollision> },
+
+    #[error("Asset '{}' has no metadata, sync it to measure its contents", .ident)]
+    MissingMetadata { ident: AssetIdent },
+
+    #[error("Asset '{}' has ID '{}' which isn't a number", .ident, .id)]
+    NonNumericId { ident: AssetIdent, id: String },
+
+    #[error("Key '{}' is reserved in this output", .key)]
+    ReservedKey { key: String },
+
+    #[error("Key '{}' can't be used as a folder name in a split output", .key.escape_debug())]
+    InvalidFolderName { key: String },
+
     #[error("File structure cannot be serialized")]
     TreeStructure,
 
+    #[error(transparent)]
+    Ignore {
+        #[from]
+        source: ignore::Error,
+    },
+
+    #[error(transparent)]
+    Template {
+        #[from]
+        source: minijinja::Error,
+    },
+
     #[error(transparent)]
     Config {
         #[from]
@@ -199,3 +735,467 @@ pub enum CodegenError {
         source: std::io::Error,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{
+        asset::Asset,
+        commands::sync::expand_dpi_variants,
+        config::{InputConfig, TargetType},
+        state::{AssetState, TargetState},
+    };
+
+    use super::*;
+
+    pub(super) fn config(options: &str) -> CodegenConfig {
+        toml::from_str(&("path = \"assets\"\n".to_string() + options)).unwrap()
+    }
+
+    /// A tree with keys and values that need escaping in every format.
+    pub(super) fn adversarial_tree() -> Value {
+        let id = |s: &str| Value::Id(AssetId::Uri(s.to_string()));
+
+        let folder = Object(BTreeMap::from([(
+            "tab\there".to_string(),
+            id("rbxasset://.runway/a\"b\\c.png"),
+        )]));
+
+        Value::Object(Object(BTreeMap::from([
+            ("say \"hi\"".to_string(), id("rbxassetid://1")),
+            ("back\\slash".to_string(), id("rbxassetid://2")),
+            ("new\nline".to_string(), id("rbxassetid://3")),
+            ("end".to_string(), id("rbxassetid://4")),
+            ("1st".to_string(), id("rbxassetid://5")),
+            ("valid_name".to_string(), id("rbxassetid://6")),
+            ("ünï\u{7}".to_string(), id("rbxassetid://7")),
+            ("folder name".to_string(), Value::Object(folder)),
+        ])))
+    }
+
+    pub(super) fn ident(path: &str) -> AssetIdent {
+        AssetIdent::from_paths(Path::new(""), Path::new(path)).unwrap()
+    }
+
+    pub(super) fn local_target() -> TargetConfig {
+        TargetConfig {
+            key: "local".to_string(),
+            r#type: TargetType::Local,
+        }
+    }
+
+    // Creates a state with each asset synced to the local target
+    pub(super) fn state(assets: &[(&str, Option<AssetMetadata>)]) -> State {
+        State {
+            assets: assets
+                .iter()
+                .map(|(path, metadata)| {
+                    let target_state = TargetState {
+                        hash: "hash".to_string(),
+                        id: format!("rbxasset://.runway/{path}"),
+                        local_path: None,
+                        synced_at: None,
+                        sprite: None,
+                        unpacked: false,
+                    };
+
+                    (
+                        ident(path),
+                        AssetState {
+                            targets: HashMap::from([("local".to_string(), target_state)]),
+                            metadata: metadata.clone(),
+                            dpi_scale: None,
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn metadata_records() {
+        let image = AssetMetadata {
+            hash: "abc".to_string(),
+            size: 1024,
+            width: Some(64),
+            height: Some(32),
+            duration: None,
+            ..Default::default()
+        };
+        let sound = AssetMetadata {
+            hash: "def".to_string(),
+            size: 2048,
+            width: None,
+            height: None,
+            duration: Some(1.5),
+            ..Default::default()
+        };
+        let state = state(&[("ui/close.png", Some(image)), ("click.ogg", Some(sound))]);
+
+        let config = config("format = \"d.ts\"\nmetadata = true");
+        let tree = generate_tree(&state, &config, &local_target()).unwrap();
+
+        let expected = r#"// This file was @generated by Runway. It is not intended for manual editing.
+declare const assets: {
+	click: {
+		id: string;
+		size: number;
+		hash: string;
+		type: string;
+		duration: number;
+	};
+	ui: {
+		close: {
+			id: string;
+			width: number;
+			height: number;
+			size: number;
+			hash: string;
+			type: string;
+		};
+	};
+};
+
+export = assets;
+"#;
+
+        assert_eq!(generate_typescript_declaration(&tree).unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_metadata() {
+        let state = state(&[("ui/close.png", None)]);
+
+        let config = config("format = \"d.ts\"\nmetadata = true");
+        let result = generate_tree(&state, &config, &local_target());
+
+        assert!(matches!(result, Err(CodegenError::MissingMetadata { .. })));
+    }
+
+    #[test]
+    fn keyed_by_target() {
+        let project: Config = toml::from_str(
+            "name = \"my-project\"\n\
+            [[target]]\ntype = \"local\"\n\
+            [[target]]\nkey = \"production\"\ntype = \"roblox\"",
+        )
+        .unwrap();
+
+        let mut state = state(&[("close.png", None)]);
+        for asset in state.assets.values_mut() {
+            asset.targets.insert(
+                "production".to_string(),
+                TargetState {
+                    hash: "hash".to_string(),
+                    id: "rbxassetid://1".to_string(),
+                    local_path: None,
+                    synced_at: None,
+                    sprite: None,
+                    unpacked: false,
+                },
+            );
+        }
+
+        let mut config = config("format = \"json\"\ntargets = [\"local\", \"production\"]");
+
+        let files = render(&project, &state, &config, &project.targets[0]).unwrap();
+        assert_eq!(
+            files,
+            vec![(
+                PathBuf::from("assets"),
+                "{\n\
+                \t\"local\": {\n\t\t\"close\": \"rbxasset://.runway/close.png\"\n\t},\n\
+                \t\"production\": {\n\t\t\"close\": \"rbxassetid://1\"\n\t}\n\
+                }\n"
+                .to_string()
+            )]
+        );
+
+        config.path = PathBuf::from("assets-{target}.json");
+
+        let files = render(&project, &state, &config, &project.targets[0]).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    PathBuf::from("assets-local.json"),
+                    "{\n\t\"close\": \"rbxasset://.runway/close.png\"\n}\n".to_string()
+                ),
+                (
+                    PathBuf::from("assets-production.json"),
+                    "{\n\t\"close\": \"rbxassetid://1\"\n}\n".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn pinned_target() {
+        let project: Config = toml::from_str(
+            "name = \"my-project\"\n\
+            [[target]]\ntype = \"local\"\n\
+            [[target]]\nkey = \"production\"\ntype = \"roblox\"",
+        )
+        .unwrap();
+
+        let state = state(&[("close.png", None)]);
+        let config = config("format = \"json\"\ntarget = \"local\"");
+
+        // Syncing production still generates the local IDs
+        let files = render(&project, &state, &config, &project.targets[1]).unwrap();
+        assert_eq!(
+            files[0].1,
+            "{\n\t\"close\": \"rbxasset://.runway/close.png\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn missing_policies() {
+        let mut state = state(&[("close.png", None), ("open.png", None)]);
+
+        // Only open.png has been uploaded to local, and only close.png to production
+        let close = state.assets.get_mut(&ident("close.png")).unwrap();
+        close.targets.clear();
+        close.targets.insert(
+            "production".to_string(),
+            TargetState {
+                hash: "hash".to_string(),
+                id: "rbxassetid://1".to_string(),
+                local_path: None,
+                synced_at: None,
+                sprite: None,
+                unpacked: false,
+            },
+        );
+
+        let generate = |options: &str| {
+            let config = config(&("format = \"json\"\n".to_string() + options));
+            let tree = generate_tree(&state, &config, &local_target())?;
+            generate_json(&tree)
+        };
+
+        assert!(matches!(
+            generate(""),
+            Err(CodegenError::MissingAsset { .. })
+        ));
+        assert_eq!(
+            generate("missing = \"skip\"").unwrap(),
+            "{\n\t\"open\": \"rbxasset://.runway/open.png\"\n}\n"
+        );
+        assert_eq!(
+            generate("missing = \"placeholder\"\nplaceholder = \"rbxassetid://0\"").unwrap(),
+            "{\n\t\"close\": \"rbxassetid://0\",\n\t\"open\": \"rbxasset://.runway/open.png\"\n}\n"
+        );
+        assert_eq!(
+            generate("missing = \"fallback\"\nfallback = \"production\"").unwrap(),
+            "{\n\t\"close\": \"rbxassetid://1\",\n\t\"open\": \"rbxasset://.runway/open.png\"\n}\n"
+        );
+        assert!(matches!(
+            generate("missing = \"fallback\"\nfallback = \"staging\""),
+            Err(CodegenError::MissingAsset { .. })
+        ));
+    }
+
+    #[test]
+    fn split() {
+        let project: Config =
+            toml::from_str("name = \"my-project\"\n[[target]]\ntype = \"local\"").unwrap();
+
+        let nested = state(&[("ui/icons/close.png", None), ("ui/open.png", None)]);
+        let config = config("format = \"ts\"\nsplit = true");
+
+        let files = render(&project, &nested, &config, &project.targets[0])
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("assets/index.ts"),
+                PathBuf::from("assets/ui/index.ts"),
+                PathBuf::from("assets/ui/icons/index.ts"),
+            ]
+        );
+
+        // Modules of folders that no longer exist are stale, but other files are left alone
+        let dir = std::env::temp_dir().join("runway-test-split");
+        let _ = fs::remove_dir_all(&dir);
+        let on_disk = CodegenConfig {
+            path: dir.clone(),
+            ..config.clone()
+        };
+        for folder in ["ui", "old/nested"] {
+            fs::create_dir_all(dir.join(folder)).unwrap();
+            fs::write(
+                dir.join(folder).join("index.ts"),
+                "// @generated by Runway\n",
+            )
+            .unwrap();
+        }
+        fs::write(dir.join("old/index.ts"), "// Written by hand\n").unwrap();
+
+        let files = render(&project, &nested, &on_disk, &project.targets[0]).unwrap();
+        assert_eq!(
+            stale_files(&on_disk, &files),
+            vec![dir.join("old/nested/index.ts")]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let invalid = state(&[("a:b/close.png", None)]);
+        let result = render(&project, &invalid, &config, &project.targets[0]);
+        assert!(matches!(
+            result,
+            Err(CodegenError::InvalidFolderName { .. })
+        ));
+    }
+
+    #[test]
+    fn sprites() {
+        let mut state = state(&[("icons/close.png", None), ("icons/open.png", None)]);
+
+        // close.png was packed into a spritesheet
+        let close = state.assets.get_mut(&ident("icons/close.png")).unwrap();
+        let target_state = close.targets.get_mut("local").unwrap();
+        target_state.id = "rbxasset://.runway/spritesheets/abc.png".to_string();
+        target_state.sprite = Some(Sprite {
+            x: 34,
+            y: 0,
+            width: 16,
+            height: 16,
+        });
+
+        let config = config("format = \"luau\"");
+        let tree = generate_tree(&state, &config, &local_target()).unwrap();
+
+        let expected = r#"-- This file was @generated by Runway. It is not intended for manual editing.
+return {
+	icons = {
+		close = {
+			id = "rbxasset://.runway/spritesheets/abc.png",
+			imageRectOffset = Vector2.new(34, 0),
+			imageRectSize = Vector2.new(16, 16),
+		},
+		open = "rbxasset://.runway/icons/open.png",
+	},
+}
+"#;
+
+        assert_eq!(generate_luau(&tree, &config).unwrap(), expected);
+    }
+
+    #[test]
+    fn trimmed_records() {
+        let image = AssetMetadata {
+            hash: "abc".to_string(),
+            size: 1024,
+            width: Some(20),
+            height: Some(10),
+            original_width: Some(64),
+            original_height: Some(64),
+            trim_x: Some(8),
+            trim_y: Some(30),
+            ..Default::default()
+        };
+        let state = state(&[("arrow.png", Some(image))]);
+
+        let config = config("format = \"luau\"\nmetadata = true");
+        let tree = generate_tree(&state, &config, &local_target()).unwrap();
+
+        let expected = r#"-- This file was @generated by Runway. It is not intended for manual editing.
+return {
+	arrow = {
+		id = "rbxasset://.runway/arrow.png",
+		width = 20,
+		height = 10,
+		size = 1024,
+		hash = "abc",
+		type = "image",
+		originalWidth = 64,
+		originalHeight = 64,
+		trimOffset = Vector2.new(8, 30),
+	},
+}
+"#;
+
+        assert_eq!(generate_luau(&tree, &config).unwrap(), expected);
+    }
+
+    #[test]
+    fn dpi_variants() {
+        let input = InputConfig {
+            dpi_scales: vec![1, 2, 3],
+            ..Default::default()
+        };
+        let mut assets = [
+            ("icons/close.png", &input),
+            ("icons/close@2x.png", &input),
+            ("icons/open@3x.png", &input),
+            ("icons/logo.png", &InputConfig::default()),
+        ]
+        .into_iter()
+        .map(|(path, input)| {
+            let asset = Asset::new(ident(path), Vec::new(), "hash".to_string(), input);
+            (ident(path), asset)
+        })
+        .collect::<BTreeMap<_, _>>();
+        expand_dpi_variants(&mut assets, &State::default());
+
+        let paths = assets.keys().map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut state = state(&paths.iter().map(|p| (p.as_str(), None)).collect::<Vec<_>>());
+        for (ident, asset) in &assets {
+            state.assets.get_mut(ident).unwrap().dpi_scale = asset.dpi_scale;
+        }
+
+        let config = config("format = \"luau\"");
+        let tree = generate_tree(&state, &config, &local_target()).unwrap();
+
+        let expected = r#"-- This file was @generated by Runway. It is not intended for manual editing.
+return {
+	icons = {
+		close = {
+			["1x"] = "rbxasset://.runway/icons/close.png",
+			["2x"] = "rbxasset://.runway/icons/close@2x.png",
+		},
+		logo = "rbxasset://.runway/icons/logo.png",
+		open = {
+			["1x"] = "rbxasset://.runway/icons/open.png",
+			["2x"] = "rbxasset://.runway/icons/open@2x.png",
+			["3x"] = "rbxasset://.runway/icons/open@3x.png",
+		},
+	},
+}
+"#;
+
+        assert_eq!(generate_luau(&tree, &config).unwrap(), expected);
+    }
+
+    #[test]
+    fn case_transform() {
+        let state = state(&[("ui/close-button.png", None), ("ui/open.png", None)]);
+
+        let config = config("format = \"json\"\ncase = \"camel\"\nstrip_extension = false");
+        let tree = generate_tree(&state, &config, &local_target()).unwrap();
+        assert_eq!(
+            generate_json(&tree).unwrap(),
+            "{\n\t\"ui\": {\n\t\t\"closeButton.png\": \"rbxasset://.runway/ui/close-button.png\",\n\t\t\"open.png\": \"rbxasset://.runway/ui/open.png\"\n\t}\n}\n"
+        );
+
+        // Files that only become the same key after the transform are collisions
+        let state = self::state(&[("my-icon.png", None), ("my_icon.png", None)]);
+        let config = self::config("format = \"json\"\ncase = \"camel\"");
+        let Err(CodegenError::Collisions { collisions }) =
+            generate_tree(&state, &config, &local_target())
+        else {
+            panic!("expected a collision");
+        };
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].key, "myIcon");
+        assert_eq!(
+            (collisions[0].first.as_ref(), collisions[0].second.as_ref()),
+            ("my-icon.png", "my_icon.png")
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Generates a Rojo `.model.json` file with an instance for each asset.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+
+use crate::{
+    asset_ident::AssetKind,
+    config::{CodegenConfig, TargetConfig},
+    state::State,
+};
+
+use super::{generate_tree_with, id::AssetId, CodegenError, Object, Record, Value};
+
+/// The class of instances generated for images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum ImageClass {
+    #[default]
+    ImageLabel,
+    Decal,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Instance<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+
+    class_name: &'static str,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<&'static str, Property<'a>>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Instance<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Property<'a> {
+    Content(&'a AssetId),
+    Vector2([u32; 2]),
+}
+
+/// Generates the tree for a model output, where every asset is a record with its type
+/// so `asset_instance` can choose its class.
+pub(super) fn generate_model_tree(
+    state: &State,
+    config: &CodegenConfig,
+    target: &TargetConfig,
+) -> Result<Value, CodegenError> {
+    generate_tree_with(state, config, target, |ident, value| {
+        // Metadata records already have it
+        if record_field(&value, "type").is_some() {
+            return value;
+        }
+
+        let mut record = match value {
+            Value::Record(record) => record,
+            id => {
+                let mut record = Record::default();
+                record.push("id", id);
+                record
+            }
+        };
+        record.push("type", Value::String(ident.kind().to_string()));
+        Value::Record(record)
+    })
+}
+
+pub(super) fn generate_model(tree: &Value, config: &CodegenConfig) -> Result<String, CodegenError> {
+    let Value::Object(root) = tree else { panic!() };
+
+    // The root's name comes from the file name
+    let instance = folder_instance(None, root, config)?;
+
+    let mut out = Vec::new();
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(b"\t"));
+    instance
+        .serialize(&mut serializer)
+        .map_err(|_| CodegenError::TreeStructure)?;
+
+    Ok(String::from_utf8(out).unwrap() + "\n")
+}
+
+fn folder_instance<'a>(
+    name: Option<&'a str>,
+    obj: &'a Object,
+    config: &CodegenConfig,
+) -> Result<Instance<'a>, CodegenError> {
+    let children = obj
+        .0
+        .iter()
+        .map(|(k, v)| match v {
+            Value::Object(subobj) => folder_instance(Some(k), subobj, config),
+            _ => asset_instance(k, v, config),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Instance {
+        name,
+        class_name: "Folder",
+        properties: BTreeMap::new(),
+        children,
+    })
+}
+
+fn asset_instance<'a>(
+    name: &'a str,
+    value: &'a Value,
+    config: &CodegenConfig,
+) -> Result<Instance<'a>, CodegenError> {
+    let id = value.id().ok_or(CodegenError::TreeStructure)?;
+
+    let image = AssetKind::Image.to_string();
+    let audio = AssetKind::Audio.to_string();
+
+    let offset = record_field(value, "imageRectOffset");
+    let size = record_field(value, "imageRectSize");
+
+    let (class_name, property) = match record_field(value, "type") {
+        // Decals can't show part of a spritesheet
+        Some(Value::String(t)) if *t == image && offset.is_some() => ("ImageLabel", "Image"),
+        Some(Value::String(t)) if *t == image => match config.image_class {
+            ImageClass::ImageLabel => ("ImageLabel", "Image"),
+            ImageClass::Decal => ("Decal", "Texture"),
+        },
+        Some(Value::String(t)) if *t == audio => ("Sound", "SoundId"),
+        _ => ("StringValue", "Value"),
+    };
+
+    let mut properties = BTreeMap::from([(property, Property::Content(id))]);
+    if let (Some(Value::Vector2(x, y)), Some(Value::Vector2(width, height))) = (offset, size) {
+        properties.insert("ImageRectOffset", Property::Vector2([*x, *y]));
+        properties.insert("ImageRectSize", Property::Vector2([*width, *height]));
+    }
+
+    Ok(Instance {
+        name: Some(name),
+        class_name,
+        properties,
+        children: Vec::new(),
+    })
+}
+
+// Reads a field of a leaf from its record
+fn record_field<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    let Value::Record(record) = value else {
+        return None;
+    };
+
+    record.iter().find_map(|(k, v)| (k == key).then_some(v))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::{config, local_target, state};
+
+    #[test]
+    fn instances() {
+        let state = state(&[
+            ("ui/close.png", None),
+            ("click.ogg", None),
+            ("tree.fbx", None),
+        ]);
+        let config = config("format = \"model.json\"\nimage_class = \"Decal\"");
+        let tree = generate_model_tree(&state, &config, &local_target()).unwrap();
+
+        let expected = r#"{
+	"ClassName": "Folder",
+	"Children": [
+		{
+			"Name": "click",
+			"ClassName": "Sound",
+			"Properties": {
+				"SoundId": "rbxasset://.runway/click.ogg"
+			}
+		},
+		{
+			"Name": "tree",
+			"ClassName": "StringValue",
+			"Properties": {
+				"Value": "rbxasset://.runway/tree.fbx"
+			}
+		},
+		{
+			"Name": "ui",
+			"ClassName": "Folder",
+			"Children": [
+				{
+					"Name": "close",
+					"ClassName": "Decal",
+					"Properties": {
+						"Texture": "rbxasset://.runway/ui/close.png"
+					}
+				}
+			]
+		}
+	]
+}
+"#;
+
+        assert_eq!(generate_model(&tree, &config).unwrap(), expected);
+    }
+}
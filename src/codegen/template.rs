@@ -0,0 +1,124 @@
+use std::fs;
+
+use minijinja::{default_auto_escape_callback, AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::config::{CodegenConfig, Config, TargetConfig};
+
+use super::{id::AssetId, CodegenError, Value};
+
+#[derive(Serialize)]
+struct Context<'a> {
+    /// The same nested tree used by the other formats.
+    tree: &'a Value,
+
+    /// Every asset in the tree with its path of keys joined by `/`.
+    entries: Vec<Entry<'a>>,
+
+    project: Project<'a>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    path: String,
+    id: &'a AssetId,
+    target: &'a str,
+}
+
+#[derive(Serialize)]
+struct Project<'a> {
+    name: &'a str,
+    runway_version: &'a str,
+}
+
+pub(super) fn generate_template(
+    tree: &Value,
+    target_trees: &[(&TargetConfig, Value)],
+    config: &CodegenConfig,
+    project: &Config,
+) -> Result<String, CodegenError> {
+    // Checked when reading the config
+    let template_path = config.template.as_ref().unwrap();
+
+    let source = fs::read_to_string(template_path)?;
+    let name = template_path.display().to_string();
+
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+
+    // Only escape HTML automatically, other formats can use filters like `tojson`
+    env.set_auto_escape_callback(|name| match default_auto_escape_callback(name) {
+        AutoEscape::Html => AutoEscape::Html,
+        _ => AutoEscape::None,
+    });
+    env.add_template(&name, &source)?;
+
+    let mut entries = Vec::new();
+    for (target, target_tree) in target_trees {
+        let Value::Object(root) = target_tree else {
+            panic!()
+        };
+
+        for (path, value) in root.leaves() {
+            entries.push(Entry {
+                path,
+                id: value.id().ok_or(CodegenError::TreeStructure)?,
+                target: &target.key,
+            });
+        }
+    }
+
+    let context = Context {
+        tree,
+        entries,
+        project: Project {
+            name: &project.name,
+            runway_version: env!("CARGO_PKG_VERSION"),
+        },
+    };
+
+    Ok(env.get_template(&name)?.render(&context)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{
+        generate_tree,
+        test::{config, local_target, state},
+    };
+
+    #[test]
+    fn render() {
+        let template_path = std::env::temp_dir().join("runway-test-template.yaml.j2");
+        fs::write(
+            &template_path,
+            "# {{ project.name }}\n\
+            {% for entry in entries %}{{ entry.path }}: {{ entry.id|tojson }} # {{ entry.target }}\n{% endfor %}\
+            close: {{ tree.ui.close }}\n",
+        )
+        .unwrap();
+
+        let codegen = config(&format!(
+            "format = \"template\"\ntemplate = {:?}",
+            template_path
+        ));
+        let project: Config = toml::from_str("name = \"my-project\"").unwrap();
+
+        let state = state(&[("ui/close.png", None), ("click.ogg", None)]);
+        let tree = generate_tree(&state, &codegen, &local_target()).unwrap();
+
+        let target = local_target();
+        let output =
+            generate_template(&tree, &[(&target, tree.clone())], &codegen, &project).unwrap();
+
+        assert_eq!(
+            output,
+            "# my-project\n\
+            click: \"rbxasset://.runway/click.ogg\" # local\n\
+            ui/close: \"rbxasset://.runway/ui/close.png\" # local\n\
+            close: rbxasset://.runway/ui/close.png\n"
+        );
+    }
+}
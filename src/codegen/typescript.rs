@@ -1,33 +1,143 @@
+use std::collections::HashSet;
+
 use super::{CodegenError, Object, Value};
 
 const HEADER_COMMENT: &str =
     "// This file was @generated by Runway. It is not intended for manual editing.";
 
 pub(super) fn generate_typescript(tree: &Value) -> Result<String, CodegenError> {
-    let Value::Object(root) = tree else { panic!() };
+    let Value::Object(_) = tree else { panic!() };
 
     let mut s = String::new();
     s.push_str(HEADER_COMMENT);
     s.push_str("\nexport default ");
-    s.push_str(&format_object(root, 0, false));
+    s.push_str(&format_value(tree, 0, false));
     s.push_str(" as const;\n");
 
     Ok(s)
 }
 
 pub(super) fn generate_typescript_declaration(tree: &Value) -> Result<String, CodegenError> {
-    let Value::Object(root) = tree else { panic!() };
+    let Value::Object(_) = tree else { panic!() };
 
     let mut s = String::new();
     s.push_str(HEADER_COMMENT);
     s.push_str("\ndeclare const assets: ");
-    s.push_str(&format_object(root, 0, true));
+    s.push_str(&format_value(tree, 0, true));
     s.push_str(";\n\nexport = assets;\n");
 
     Ok(s)
 }
 
-fn format_object(obj: &Object, indent_level: usize, declaration: bool) -> String {
+/// Generates one module of a split output, which imports the modules of its subfolders.
+pub(super) fn generate_typescript_module(obj: &Object) -> String {
+    let mut imports = String::new();
+    let mut names = HashSet::new();
+
+    let mut s = String::new();
+    s.push_str("export default {\n");
+
+    for (k, v) in &obj.0 {
+        s.push('\t');
+        s.push_str(&format_key(k));
+        s.push_str(": ");
+
+        if let Value::Object(_) = v {
+            let name = import_name(k, &names);
+            imports.push_str(&format!(
+                "import {} from {};\n",
+                name,
+                format_string("./".to_string() + k)
+            ));
+            s.push_str(&name);
+            names.insert(name);
+        } else {
+            s.push_str(&format_value(v, 1, false));
+        }
+
+        s.push_str(",\n");
+    }
+
+    s.push_str("} as const;\n");
+
+    let mut out = HEADER_COMMENT.to_string();
+    out.push('\n');
+    if !imports.is_empty() {
+        out.push_str(&imports);
+        out.push('\n');
+    }
+    out.push_str(&s);
+    out
+}
+
+// Words that can't be used as an import's name
+const RESERVED_WORDS: &str = "await break case catch class const continue debugger default \
+    delete do else enum export extends false finally for function if implements import in \
+    instanceof interface let new null package private protected public return static super \
+    switch this throw true try typeof var void while with yield";
+
+// Names a folder's import after its key, e.g. `folder name` becomes `folder_name`,
+// adding a number if the name is already taken
+fn import_name(key: &str, taken: &HashSet<String>) -> String {
+    let mut name = key
+        .chars()
+        .map(|c| if is_id_part(c) { c } else { '_' })
+        .collect::<String>();
+    if !name.starts_with(is_id_start) {
+        name.insert(0, '_');
+    }
+    if RESERVED_WORDS.split_whitespace().any(|word| word == name) {
+        name.push('_');
+    }
+
+    let mut unique = name.clone();
+    let mut n = 2;
+    while taken.contains(&unique) {
+        unique = format!("{name}_{n}");
+        n += 1;
+    }
+    unique
+}
+
+fn format_value(value: &Value, indent_level: usize, declaration: bool) -> String {
+    match value {
+        Value::Object(obj) => format_object(obj.0.iter(), indent_level, declaration),
+        Value::Record(record) => format_object(record.iter(), indent_level, declaration),
+        Value::Id(id) => {
+            if declaration {
+                id.format().type_name().to_string()
+            } else {
+                id.to_expression(|s| format_string(s))
+            }
+        }
+        Value::String(s) => {
+            if declaration {
+                "string".to_string()
+            } else {
+                format_string(s)
+            }
+        }
+        Value::Number(n) => {
+            if declaration {
+                "number".to_string()
+            } else {
+                n.to_string()
+            }
+        }
+        Value::Vector2(x, y) => {
+            if declaration {
+                "Vector2".to_string()
+            } else {
+                format!("new Vector2({x}, {y})")
+            }
+        }
+    }
+}
+
+fn format_object<'a, I>(entries: I, indent_level: usize, declaration: bool) -> String
+where
+    I: Iterator<Item = (&'a String, &'a Value)>,
+{
     let indent = "\t".repeat(indent_level);
     let indent_plus1 = "\t".repeat(indent_level + 1);
 
@@ -39,27 +149,11 @@ fn format_object(obj: &Object, indent_level: usize, declaration: bool) -> String
     let mut s = String::new();
     s.push_str("{\n");
 
-    let iter = obj.0.iter().peekable();
-
-    for (k, v) in iter {
+    for (k, v) in entries {
         s.push_str(&(indent_plus1.clone() + &format_key(k) + ": "));
-
-        match v {
-            Value::Object(subobj) => {
-                s.push_str(&format_object(subobj, indent_level + 1, declaration));
-                s.push(line_ending);
-                s.push('\n');
-            }
-            Value::Id(id) => {
-                if declaration {
-                    s.push_str("string");
-                } else {
-                    s.push_str(&format_string(id));
-                }
-                s.push(line_ending);
-                s.push('\n');
-            }
-        }
+        s.push_str(&format_value(v, indent_level + 1, declaration));
+        s.push(line_ending);
+        s.push('\n');
     }
 
     s.push_str(&(indent + "}"));
@@ -88,5 +182,109 @@ fn format_key<S: AsRef<str>>(s: S) -> String {
 }
 
 fn format_string<S: AsRef<str>>(s: S) -> String {
-    "\"".to_string() + s.as_ref() + "\""
+    let mut out = String::with_capacity(s.as_ref().len() + 2);
+    out.push('"');
+
+    for c in s.as_ref().chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            // Line terminators aren't allowed in string literals before ES2019
+            '\u{2028}' | '\u{2029}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::test::adversarial_tree;
+
+    #[test]
+    fn module() {
+        let Value::Object(root) = adversarial_tree() else {
+            panic!()
+        };
+
+        let expected = r#"// This file was @generated by Runway. It is not intended for manual editing.
+import folder_name from "./folder name";
+
+export default {
+	"1st": "rbxassetid://5",
+	"back\\slash": "rbxassetid://2",
+	end: "rbxassetid://4",
+	"folder name": folder_name,
+	"new\nline": "rbxassetid://3",
+	"say \"hi\"": "rbxassetid://1",
+	valid_name: "rbxassetid://6",
+	"ünï\u0007": "rbxassetid://7",
+} as const;
+"#;
+
+        assert_eq!(generate_typescript_module(&root), expected);
+    }
+
+    #[test]
+    fn import_names() {
+        let taken = HashSet::from(["icons".to_string()]);
+        assert_eq!(import_name("close buttons", &taken), "close_buttons");
+        assert_eq!(import_name("2x", &taken), "_2x");
+        assert_eq!(import_name("default", &taken), "default_");
+        assert_eq!(import_name("icons", &taken), "icons_2");
+    }
+
+    #[test]
+    fn escaping() {
+        let expected = r#"// This file was @generated by Runway. It is not intended for manual editing.
+export default {
+	"1st": "rbxassetid://5",
+	"back\\slash": "rbxassetid://2",
+	end: "rbxassetid://4",
+	"folder name": {
+		"tab\there": "rbxasset://.runway/a\"b\\c.png",
+	},
+	"new\nline": "rbxassetid://3",
+	"say \"hi\"": "rbxassetid://1",
+	valid_name: "rbxassetid://6",
+	"ünï\u0007": "rbxassetid://7",
+} as const;
+"#;
+
+        assert_eq!(generate_typescript(&adversarial_tree()).unwrap(), expected);
+    }
+
+    #[test]
+    fn escaping_declaration() {
+        let expected = r#"// This file was @generated by Runway. It is not intended for manual editing.
+declare const assets: {
+	"1st": string;
+	"back\\slash": string;
+	end: string;
+	"folder name": {
+		"tab\there": string;
+	};
+	"new\nline": string;
+	"say \"hi\"": string;
+	valid_name: string;
+	"ünï\u0007": string;
+};
+
+export = assets;
+"#;
+
+        assert_eq!(
+            generate_typescript_declaration(&adversarial_tree()).unwrap(),
+            expected
+        );
+    }
 }
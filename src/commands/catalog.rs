@@ -0,0 +1,404 @@
+use std::{collections::BTreeMap, fs};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ignore::overrides::OverrideBuilder;
+use minijinja::Environment;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    asset::Asset,
+    asset_ident::{AssetIdent, AssetKind},
+    cli::CatalogOptions,
+    config::{Config, ConfigError},
+    preprocess::{measure_unprocessed, thumbnail},
+    state::{AssetState, State, StateError},
+};
+
+use super::sync::{configure_walker, expand_dpi_variants, generate_asset_hash, InputMatcher};
+
+const TEMPLATE: &str = include_str!("catalog.html");
+
+// Thumbnails are shrunk to fit in a square of this size
+const THUMBNAIL_SIZE: u32 = 96;
+
+#[derive(Serialize)]
+struct Catalog<'a> {
+    project: &'a str,
+    thumbnail_size: u32,
+    targets: Vec<&'a str>,
+    assets: Vec<CatalogAsset>,
+}
+
+#[derive(Serialize)]
+struct CatalogAsset {
+    ident: AssetIdent,
+    #[serde(rename = "type")]
+    kind: AssetKind,
+
+    // Whether the file still exists
+    exists: bool,
+
+    // A PNG data URI
+    thumbnail: Option<String>,
+
+    width: Option<u32>,
+    height: Option<u32>,
+    duration: Option<String>,
+    size: Option<String>,
+
+    targets: Vec<CatalogTarget>,
+
+    // Lowercase text the search box matches against
+    search: String,
+}
+
+#[derive(Serialize)]
+struct CatalogTarget {
+    id: Option<String>,
+    link: Option<String>,
+    status: &'static str,
+    status_text: &'static str,
+}
+
+pub async fn catalog(options: CatalogOptions) -> Result<(), CatalogError> {
+    let config_path = match &options.config {
+        Some(c) => c.to_owned(),
+        None => std::env::current_dir()?,
+    };
+    let config = Config::read_from_folder_or_file(config_path)?;
+
+    log::debug!("Loaded config at '{}'", config.file_path.display());
+
+    let state = State::read_from_config(&config)?;
+
+    // Assets that have been synced before but no longer exist are listed too
+    let mut files: BTreeMap<AssetIdent, Option<Asset>> = state
+        .assets
+        .keys()
+        .map(|ident| (ident.clone(), None))
+        .collect();
+    files.extend(
+        find_assets(&config, &state)?
+            .into_iter()
+            .map(|(ident, asset)| (ident, Some(asset))),
+    );
+
+    let assets = files
+        .into_iter()
+        .map(|(ident, asset)| {
+            let asset_state = state.assets.get(&ident);
+            catalog_asset(&config, ident, asset, asset_state)
+        })
+        .collect();
+
+    let catalog = Catalog {
+        project: &config.name,
+        thumbnail_size: THUMBNAIL_SIZE,
+        targets: config.targets.iter().map(|t| t.key.as_str()).collect(),
+        assets,
+    };
+
+    let contents = render_catalog(&catalog)?;
+
+    let output = options
+        .output
+        .unwrap_or_else(|| config.root_path().join("runway-catalog.html"));
+    fs::write(&output, contents)?;
+
+    log::info!(
+        "Wrote catalog of {} assets to {}",
+        catalog.assets.len(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn render_catalog(catalog: &Catalog) -> Result<String, minijinja::Error> {
+    // Templates ending in `.html` are escaped automatically
+    let mut env = Environment::new();
+    env.add_template("catalog.html", TEMPLATE)?;
+    env.get_template("catalog.html")?.render(catalog)
+}
+
+// Reads every file matched by an input, along with generated DPI variants
+fn find_assets(
+    config: &Config,
+    state: &State,
+) -> Result<BTreeMap<AssetIdent, Asset>, CatalogError> {
+    let root = config.root_path().to_path_buf();
+
+    let mut builder = OverrideBuilder::new(&root);
+    for input in &config.inputs {
+        builder.add(&input.glob)?;
+    }
+    let overrides = builder.build()?;
+    let matcher = InputMatcher::new(config)?;
+
+    let mut assets = BTreeMap::new();
+
+    for result in configure_walker(&root, overrides).build() {
+        let file = result?;
+        if file.metadata()?.is_dir() {
+            continue;
+        }
+        let Some(input) = matcher.find(file.path()) else {
+            continue;
+        };
+
+        let Ok(ident) = AssetIdent::from_paths(&root, file.path()) else {
+            log::warn!("Matched file at {} is not supported", file.path().display());
+            continue;
+        };
+
+        let contents = fs::read(file.path())?;
+        let hash = generate_asset_hash(&contents, input);
+        assets.insert(ident.clone(), Asset::new(ident, contents, hash, input));
+    }
+
+    expand_dpi_variants(&mut assets, state);
+
+    Ok(assets)
+}
+
+fn catalog_asset(
+    config: &Config,
+    ident: AssetIdent,
+    asset: Option<Asset>,
+    asset_state: Option<&AssetState>,
+) -> CatalogAsset {
+    let exists = asset.is_some();
+
+    // Thumbnails are made from the source file, since processing can be slow
+    let thumbnail = match &asset {
+        Some(asset) => match thumbnail(asset, THUMBNAIL_SIZE) {
+            Ok(png) => png.map(|png| format!("data:image/png;base64,{}", STANDARD.encode(png))),
+            Err(e) => {
+                log::warn!("Failed to create thumbnail for {}: {}", ident, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Use the last synced measurements if the file hasn't changed since or is missing
+    let synced_metadata = asset_state.and_then(|s| s.metadata.clone());
+    let metadata = match &asset {
+        Some(asset) => match synced_metadata.filter(|m| m.hash == asset.hash) {
+            Some(metadata) => Some(metadata),
+            None => match &asset.metadata {
+                Some(metadata) => Some(metadata.clone()),
+                None => measure_unprocessed(asset)
+                    .map_err(|e| log::warn!("Failed to measure {}: {}", ident, e))
+                    .ok(),
+            },
+        },
+        None => synced_metadata,
+    };
+    let metadata = metadata.as_ref();
+
+    let mut search = ident.to_string() + " " + &ident.kind().to_string();
+
+    let targets = config
+        .targets
+        .iter()
+        .map(|target| {
+            let target_state = asset_state.and_then(|s| s.targets.get(&target.key));
+
+            let (status, status_text) = match (target_state, &asset) {
+                (None, _) => ("unsynced", "Not synced"),
+                (Some(_), None) => ("missing", "Synced, file missing"),
+                (Some(t), Some(a)) if t.hash == a.hash => ("synced", "Synced"),
+                (Some(_), Some(_)) => ("changed", "Changed since sync"),
+            };
+
+            let id = target_state.map(|t| t.id.clone());
+            if let Some(id) = &id {
+                search.push(' ');
+                search.push_str(id);
+            }
+
+            CatalogTarget {
+                link: id.as_deref().and_then(dashboard_link),
+                id,
+                status,
+                status_text,
+            }
+        })
+        .collect();
+
+    CatalogAsset {
+        kind: ident.kind(),
+        exists,
+        thumbnail,
+        width: metadata.and_then(|m| m.width),
+        height: metadata.and_then(|m| m.height),
+        duration: metadata
+            .and_then(|m| m.duration)
+            .map(|d| format!("{d:.2}s")),
+        size: metadata.map(|m| format_size(m.size)),
+        targets,
+        search: search.to_lowercase(),
+        ident,
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
+
+// Returns the Creator Dashboard page for a Roblox asset
+fn dashboard_link(id: &str) -> Option<String> {
+    let number = id.strip_prefix("rbxassetid://")?;
+    Some(format!(
+        "https://create.roblox.com/dashboard/creations/store/{number}/configure"
+    ))
+}
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error(transparent)]
+    Config {
+        #[from]
+        source: ConfigError,
+    },
+
+    #[error(transparent)]
+    State {
+        #[from]
+        source: StateError,
+    },
+
+    #[error(transparent)]
+    Template {
+        #[from]
+        source: minijinja::Error,
+    },
+
+    #[error(transparent)]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+
+    #[error(transparent)]
+    Ignore {
+        #[from]
+        source: ignore::Error,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::Path};
+
+    use super::*;
+    use crate::state::{AssetMetadata, TargetState};
+
+    // A red PNG twice as wide as it is tall
+    fn png() -> Vec<u8> {
+        let mut contents = Vec::new();
+        let mut encoder = png::Encoder::new(&mut contents, 200, 100);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 0, 0, 255].repeat(200 * 100))
+            .unwrap();
+        writer.finish().unwrap();
+        contents
+    }
+
+    #[test]
+    fn render() {
+        let config: Config = toml::from_str(
+            "name = \"my-project\"\n\
+            [[target]]\nkey = \"production\"\ntype = \"roblox\"\n\
+            [[target]]\nkey = \"staging\"\ntype = \"roblox\"",
+        )
+        .unwrap();
+
+        let ident = AssetIdent::from_paths(Path::new(""), Path::new("ui/<close>.png")).unwrap();
+        let asset = Asset::new(ident.clone(), png(), "abc".to_string(), &Default::default());
+        let asset_state = AssetState {
+            targets: HashMap::from([(
+                "production".to_string(),
+                TargetState {
+                    hash: "abc".to_string(),
+                    id: "rbxassetid://123".to_string(),
+                    local_path: None,
+                    synced_at: None,
+                    sprite: None,
+                    unpacked: false,
+                },
+            )]),
+            metadata: None,
+            dpi_scale: None,
+        };
+
+        let asset = catalog_asset(&config, ident, Some(asset), Some(&asset_state));
+        assert_eq!((asset.width, asset.height), (Some(200), Some(100)));
+
+        // Thumbnails are shrunk to fit
+        let thumbnail = asset.thumbnail.clone().unwrap();
+        let png = STANDARD
+            .decode(thumbnail.strip_prefix("data:image/png;base64,").unwrap())
+            .unwrap();
+        let size = imagesize::blob_size(&png).unwrap();
+        assert_eq!((size.width, size.height), (96, 48));
+
+        let html = render_catalog(&Catalog {
+            project: "my-project",
+            thumbnail_size: THUMBNAIL_SIZE,
+            targets: vec!["production", "staging"],
+            assets: vec![asset],
+        })
+        .unwrap();
+
+        // Slashes are escaped too, which browsers decode in attributes
+        let escape_slashes = |s: &str| s.replace('/', "&#x2f;");
+        assert!(html.contains("<code>ui&#x2f;&lt;close&gt;.png</code>"));
+        assert!(!html.contains("<close>"));
+        assert!(html.contains(&format!("<img src=\"{}\"", escape_slashes(&thumbnail))));
+        assert!(html.contains("<span class=\"synced\">Synced</span>"));
+        assert!(html.contains("<span class=\"unsynced\">Not synced</span>"));
+        assert!(html.contains(&escape_slashes("/store/123/configure")));
+    }
+
+    #[test]
+    fn synced_metadata() {
+        let config: Config = toml::from_str("name = \"my-project\"").unwrap();
+        let ident = AssetIdent::from_paths(Path::new(""), Path::new("icon.png")).unwrap();
+        let asset_state = AssetState {
+            targets: HashMap::new(),
+            metadata: Some(AssetMetadata {
+                hash: "abc".to_string(),
+                size: 512,
+                width: Some(64),
+                height: Some(32),
+                ..Default::default()
+            }),
+            dpi_scale: None,
+        };
+
+        // Measurements from the last sync are used while the file is unchanged
+        let asset = Asset::new(ident.clone(), png(), "abc".to_string(), &Default::default());
+        let catalog = catalog_asset(&config, ident.clone(), Some(asset), Some(&asset_state));
+        assert_eq!((catalog.width, catalog.height), (Some(64), Some(32)));
+        assert_eq!(catalog.size.as_deref(), Some("512 B"));
+
+        // Changed files are measured again
+        let asset = Asset::new(ident.clone(), png(), "def".to_string(), &Default::default());
+        let catalog = catalog_asset(&config, ident.clone(), Some(asset), Some(&asset_state));
+        assert_eq!((catalog.width, catalog.height), (Some(200), Some(100)));
+
+        // Missing files keep their last measurements
+        let catalog = catalog_asset(&config, ident, None, Some(&asset_state));
+        assert_eq!((catalog.width, catalog.height), (Some(64), Some(32)));
+    }
+}
@@ -0,0 +1,190 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use ignore::overrides::OverrideBuilder;
+use similar::TextDiff;
+use thiserror::Error;
+
+use crate::{
+    asset::Asset,
+    asset_ident::AssetIdent,
+    cli::CheckOptions,
+    codegen,
+    config::{Config, ConfigError, TargetConfig},
+    state::{State, StateError},
+};
+
+use super::sync::{configure_walker, expand_dpi_variants, generate_asset_hash, InputMatcher};
+
+pub async fn check(options: CheckOptions) -> Result<(), CheckError> {
+    let config_path = match &options.project.config {
+        Some(c) => c.to_owned(),
+        None => std::env::current_dir()?,
+    };
+    let config = Config::read_from_folder_or_file(config_path)?;
+
+    log::debug!("Loaded config at '{}'", config.file_path.display());
+
+    let target = config
+        .targets
+        .clone()
+        .into_iter()
+        .find(|t| t.key == options.project.target)
+        .ok_or(ConfigError::UnknownTarget)?;
+
+    let state = State::read_from_config(&config)?;
+
+    let mut problems = check_assets(&config, &state, &target)?;
+    problems += check_codegen(&config, &state, &target);
+
+    if problems == 0 {
+        log::info!("Everything is synced for target '{}'", target.key);
+        Ok(())
+    } else {
+        Err(CheckError::Failed { problems })
+    }
+}
+
+// Checks that every input has been synced in its current version
+fn check_assets(
+    config: &Config,
+    state: &State,
+    target: &TargetConfig,
+) -> Result<usize, CheckError> {
+    let root = config.root_path().to_path_buf();
+
+    let mut builder = OverrideBuilder::new(&root);
+    for input in &config.inputs {
+        builder.add(&input.glob)?;
+    }
+    let overrides = builder.build()?;
+    let matcher = InputMatcher::new(config)?;
+
+    let mut problems = 0;
+    let mut assets = BTreeMap::new();
+
+    for result in configure_walker(&root, overrides).build() {
+        let file = result?;
+        if file.metadata()?.is_dir() {
+            continue;
+        }
+        let Some(input) = matcher.find(file.path()) else {
+            continue;
+        };
+
+        let Ok(ident) = AssetIdent::from_paths(&root, file.path()) else {
+            log::error!("Matched file at {} is not supported", file.path().display());
+            problems += 1;
+            continue;
+        };
+
+        let contents = fs::read(file.path())?;
+        let hash = generate_asset_hash(&contents, input);
+        assets.insert(ident.clone(), Asset::new(ident, contents, hash, input));
+    }
+
+    expand_dpi_variants(&mut assets, state);
+
+    for (ident, asset) in &assets {
+        let target_state = state
+            .assets
+            .get(ident)
+            .and_then(|asset| asset.targets.get(&target.key));
+
+        match target_state {
+            None => {
+                log::error!("Asset '{}' has no ID for target '{}'", ident, target.key);
+                problems += 1;
+            }
+            Some(target_state) if target_state.hash != asset.hash => {
+                log::error!("Asset '{}' has changed since it was synced", ident);
+                problems += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(problems)
+}
+
+// Checks that every codegen output on disk matches what would be generated
+fn check_codegen(config: &Config, state: &State, target: &TargetConfig) -> usize {
+    let mut problems = 0;
+
+    for codegen in &config.codegens {
+        let files = match codegen::render(config, state, codegen, target) {
+            Ok(files) => files,
+            Err(e) => {
+                log::error!(
+                    "Failed to generate output at {}: {}",
+                    codegen.path.display(),
+                    e
+                );
+                problems += 1;
+                continue;
+            }
+        };
+
+        for path in codegen::stale_files(codegen, &files) {
+            log::error!(
+                "Codegen output at {} is no longer generated",
+                path.display()
+            );
+            problems += 1;
+        }
+
+        for (path, expected) in files {
+            let actual = fs::read_to_string(&path).unwrap_or_default();
+            if actual == expected {
+                continue;
+            }
+
+            log::error!("Codegen output at {} is out of date", path.display());
+            print_diff(&path, &actual, &expected);
+            problems += 1;
+        }
+    }
+
+    problems
+}
+
+fn print_diff(path: &Path, actual: &str, expected: &str) {
+    let name = path.display().to_string();
+    let diff = TextDiff::from_lines(actual, expected);
+
+    print!(
+        "{}",
+        diff.unified_diff()
+            .context_radius(3)
+            .header(&name, &format!("{name} (expected)"))
+    );
+}
+
+#[derive(Debug, Error)]
+pub enum CheckError {
+    #[error("Check failed with {} problem(s)", .problems)]
+    Failed { problems: usize },
+
+    #[error(transparent)]
+    Config {
+        #[from]
+        source: ConfigError,
+    },
+
+    #[error(transparent)]
+    State {
+        #[from]
+        source: StateError,
+    },
+
+    #[error(transparent)]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+
+    #[error(transparent)]
+    Ignore {
+        #[from]
+        source: ignore::Error,
+    },
+}
@@ -1,7 +1,11 @@
+mod catalog;
+mod check;
 mod codegen;
-mod sync;
+pub(crate) mod sync;
 mod watch;
 
+pub use catalog::catalog;
+pub use check::check;
 pub use codegen::codegen;
 pub use sync::{sync, sync_with_config, SyncError};
 pub use watch::{watch, WatchError};
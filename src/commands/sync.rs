@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashSet},
     fs,
     path::{Path, PathBuf},
     sync::Arc,
@@ -30,10 +30,11 @@ use crate::{
     asset::Asset,
     asset_ident::{replace_slashes, AssetIdent},
     cli::SyncOptions,
-    codegen,
-    config::{Config, ConfigError, TargetConfig, TargetType},
-    preprocess::{preprocess, PreprocessError},
-    state::{AssetState, State, StateError, TargetState},
+    codegen::{self, CodegenFormat},
+    config::{Config, ConfigError, InputConfig, TargetConfig, TargetType},
+    dpi::plan_variants,
+    preprocess::{measure_unprocessed, pack_assets, preprocess, PreprocessError},
+    state::{AssetState, Sprite, State, StateError, TargetState},
     symlink::{symlink_content_folders, SymlinkError},
 };
 
@@ -46,6 +47,12 @@ struct SyncSession {
 
     assets: BTreeMap<AssetIdent, Asset>,
 
+    // Spritesheets added to `assets` for this sync, with the images packed into each
+    spritesheets: Vec<(AssetIdent, Vec<(AssetIdent, Sprite)>)>,
+
+    // Images from inputs with `pack` that are synced on their own this sync
+    unpacked: Vec<AssetIdent>,
+
     // Errors encountered and ignored during syncing.
     errors: Vec<anyhow::Error>,
 }
@@ -112,7 +119,10 @@ pub async fn sync_with_config(
     let mut session = SyncSession::new(options, config, target)?;
 
     session.find_assets()?;
+    session.pack_sprites();
     session.perform_sync(strategy).await?;
+    session.unpack_sprites();
+    session.measure_assets();
 
     let state = session.write_state()?;
 
@@ -148,6 +158,35 @@ pub fn configure_walker(root: &PathBuf, overrides: Override) -> WalkBuilder {
     builder
 }
 
+/// Finds which input a matched file belongs to, so its processing options can be applied.
+/// When several inputs match a file, the first one in the config wins.
+pub(super) struct InputMatcher<'a> {
+    inputs: Vec<(Override, &'a InputConfig)>,
+}
+
+impl<'a> InputMatcher<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, ignore::Error> {
+        let inputs = config
+            .inputs
+            .iter()
+            .map(|input| {
+                let mut builder = OverrideBuilder::new(config.root_path());
+                builder.add(&input.glob)?;
+                Ok((builder.build()?, input))
+            })
+            .collect::<Result<_, ignore::Error>>()?;
+
+        Ok(Self { inputs })
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&'a InputConfig> {
+        self.inputs
+            .iter()
+            .find(|(overrides, _)| overrides.matched(path, false).is_whitelist())
+            .map(|(_, input)| *input)
+    }
+}
+
 impl SyncSession {
     fn new(
         options: &SyncOptions,
@@ -170,6 +209,8 @@ impl SyncSession {
             target: target.clone(),
             force_sync: options.force,
             assets: BTreeMap::new(),
+            spritesheets: Vec::new(),
+            unpacked: Vec::new(),
             errors: Vec::new(),
         })
     }
@@ -188,24 +229,36 @@ impl SyncSession {
         let overrides = builder.build()?;
 
         let walker = configure_walker(&root, overrides).build();
+        let matcher = InputMatcher::new(&self.config)?;
 
         for result in walker {
             match result {
                 Ok(file) => {
-                    match Self::process_entry(&self.prev_state, self.config.root_path(), file) {
+                    let Some(input) = matcher.find(file.path()) else {
+                        continue;
+                    };
+
+                    match Self::process_entry(
+                        &self.prev_state,
+                        self.config.root_path(),
+                        input,
+                        file,
+                    ) {
                         Ok(Some(i)) => {
                             log::trace!("Found asset '{}'", i.ident);
 
                             self.assets.insert(i.ident.clone(), i);
                         }
                         Ok(None) => {}
-                        Err(e) => self.raise_error(e),
+                        Err(e) => raise_error(e, &mut self.errors),
                     }
                 }
-                Err(e) => self.raise_error(e),
+                Err(e) => raise_error(e, &mut self.errors),
             }
         }
 
+        expand_dpi_variants(&mut self.assets, &self.prev_state);
+
         log::debug!("Found {} assets", self.assets.len());
 
         Ok(())
@@ -214,6 +267,7 @@ impl SyncSession {
     fn process_entry(
         prev_state: &State,
         root_path: &Path,
+        input: &InputConfig,
         file: DirEntry,
     ) -> Result<Option<Asset>, SyncError> {
         if file.metadata()?.is_dir() {
@@ -229,22 +283,12 @@ impl SyncSession {
 
         let contents = fs::read(file.path())?;
 
-        // Read previous target state from file if available
-        let targets = {
-            if let Some(prev) = prev_state.assets.get(&ident) {
-                prev.targets.clone()
-            } else {
-                HashMap::new()
-            }
-        };
+        let hash = generate_asset_hash(&contents, input);
 
-        Ok(Some(Asset {
-            ident,
-            path: file.path().to_path_buf(),
-            hash: generate_asset_hash(&contents),
-            contents: contents.into(),
-            targets,
-        }))
+        let mut asset = Asset::new(ident, contents, hash, input);
+        restore_prev_state(&mut asset, prev_state);
+
+        Ok(Some(asset))
     }
 
     async fn perform_sync(&mut self, strategy: Box<dyn SyncStrategy>) -> Result<(), SyncError> {
@@ -257,9 +301,190 @@ impl SyncSession {
             err_count,
             skip_count,
         );
+
+        // Only count files that were synced, not images packed into spritesheets
+        let bytes_saved = self
+            .assets
+            .values()
+            .filter(|asset| !asset.packed)
+            .map(|asset| asset.bytes_saved)
+            .sum::<u64>();
+        if bytes_saved > 0 {
+            log::info!("PNG optimization saved {} bytes", bytes_saved);
+        }
         Ok(())
     }
 
+    // Packs images from inputs with `pack` into spritesheets, which are synced in their place.
+    // Only spritesheets containing an image that changed are packed again.
+    fn pack_sprites(&mut self) {
+        let check_local_path = self.target.r#type == TargetType::Local;
+
+        let mut candidates = Vec::new();
+        let mut changed = HashSet::new();
+
+        for (ident, asset) in self.assets.iter_mut() {
+            if !asset.input.pack
+                || !matches!(
+                    ident.asset_type(),
+                    AssetType::DecalPng | AssetType::DecalJpeg
+                )
+            {
+                continue;
+            }
+
+            asset.packed = true;
+
+            // Images synced on their own before `pack` was enabled are packed now
+            let prev = asset.targets.get(&self.target.key);
+            let needs_sync = Self::needs_sync(
+                self.force_sync,
+                ident,
+                asset,
+                &self.prev_state,
+                &self.target,
+                check_local_path,
+            ) || prev.is_some_and(|prev| prev.sprite.is_none() && !prev.unpacked);
+
+            if needs_sync {
+                if let Some(prev) = prev {
+                    changed.insert(prev.id.clone());
+                }
+            }
+            candidates.push((ident.clone(), needs_sync));
+        }
+
+        // Images sharing a spritesheet with a changed image are packed again with it
+        let repack = candidates
+            .into_iter()
+            .filter(|(ident, needs_sync)| {
+                *needs_sync
+                    || self.assets[ident]
+                        .targets
+                        .get(&self.target.key)
+                        .is_some_and(|prev| changed.contains(&prev.id))
+            })
+            .map(|(ident, _)| ident)
+            .collect::<Vec<_>>();
+
+        if repack.is_empty() {
+            return;
+        }
+
+        let mut packable = Vec::new();
+        for ident in repack {
+            let asset = self.assets.get_mut(&ident).unwrap();
+            match preprocess(asset) {
+                Ok(()) => packable.push(ident),
+                Err(e) => raise_error(e, &mut self.errors),
+            }
+        }
+
+        let assets = packable
+            .iter()
+            .map(|ident| &self.assets[ident])
+            .collect::<Vec<_>>();
+        let (spritesheets, unpacked) = pack_assets(&assets);
+
+        for i in unpacked {
+            self.assets.get_mut(&packable[i]).unwrap().packed = false;
+            self.unpacked.push(packable[i].clone());
+        }
+
+        log::info!(
+            "Packed {} images into {} spritesheets",
+            spritesheets.iter().map(|s| s.sprites.len()).sum::<usize>(),
+            spritesheets.len()
+        );
+
+        for spritesheet in spritesheets {
+            let hash = blake3::hash(&spritesheet.contents).to_hex().to_string();
+            let ident = AssetIdent::generated(&format!("spritesheets/{}.png", &hash[..16]));
+
+            let sprites = spritesheet
+                .sprites
+                .into_iter()
+                .map(|(i, sprite)| (packable[i].clone(), sprite))
+                .collect::<Vec<_>>();
+
+            // Optimize the spritesheet as much as any of its images would have been
+            let input = InputConfig {
+                optimize: sprites
+                    .iter()
+                    .filter_map(|(ident, _)| self.assets[ident].input.optimize)
+                    .max(),
+                ..Default::default()
+            };
+
+            self.assets.insert(
+                ident.clone(),
+                Asset::new(ident.clone(), spritesheet.contents, hash, &input),
+            );
+            self.spritesheets.push((ident, sprites));
+        }
+    }
+
+    // Gives packed images the IDs of their synced spritesheets, then removes the spritesheets.
+    // Images that were synced on their own are marked so they aren't packed again until they change.
+    fn unpack_sprites(&mut self) {
+        for ident in std::mem::take(&mut self.unpacked) {
+            let asset = self.assets.get_mut(&ident).unwrap();
+            if let Some(target_state) = asset.targets.get_mut(&self.target.key) {
+                if target_state.hash == asset.hash {
+                    target_state.unpacked = true;
+                }
+            }
+        }
+
+        for (ident, sprites) in std::mem::take(&mut self.spritesheets) {
+            let spritesheet = self.assets.remove(&ident).unwrap();
+
+            // Keep the previous state of the images if the spritesheet failed to sync
+            let Some(target_state) = spritesheet.targets.get(&self.target.key) else {
+                continue;
+            };
+
+            for (ident, sprite) in sprites {
+                let asset = self.assets.get_mut(&ident).unwrap();
+                asset.targets.insert(
+                    self.target.key.clone(),
+                    TargetState {
+                        hash: asset.hash.clone(),
+                        sprite: Some(sprite),
+                        ..target_state.clone()
+                    },
+                );
+            }
+        }
+    }
+
+    // Measure assets that weren't synced and don't have metadata yet, e.g. from older states.
+    // Measurements are only used by codegen, so failing to measure doesn't fail the sync.
+    fn measure_assets(&mut self) {
+        let uses_metadata = self
+            .config
+            .codegens
+            .iter()
+            .any(|c| c.metadata || matches!(c.format, CodegenFormat::Manifest));
+        if !uses_metadata {
+            return;
+        }
+
+        for asset in self.assets.values_mut() {
+            // Assets that failed to preprocess while syncing have already been reported
+            if asset.metadata.is_some() || asset.preprocessed {
+                continue;
+            }
+
+            log::debug!("Measuring {}", asset.ident);
+
+            match measure_unprocessed(asset) {
+                Ok(metadata) => asset.metadata = Some(metadata),
+                Err(e) => log::warn!("Failed to measure {}: {}", asset.ident, e),
+            }
+        }
+    }
+
     fn iter_needs_sync<'a>(
         force: &'a bool,
         assets: &'a mut BTreeMap<AssetIdent, Asset>,
@@ -268,44 +493,57 @@ impl SyncSession {
         check_local_path: &'a bool,
     ) -> Box<dyn Iterator<Item = (&'a AssetIdent, &'a mut Asset)> + 'a + Send> {
         Box::new(assets.iter_mut().filter(|(ident, asset)| {
-            if *force {
-                log::trace!("Asset '{}' will sync (forced)", ident);
-                return true;
-            }
+            // Packed images are synced as part of their spritesheet
+            !asset.packed
+                && Self::needs_sync(*force, ident, asset, prev_state, target, *check_local_path)
+        }))
+    }
+
+    fn needs_sync(
+        force: bool,
+        ident: &AssetIdent,
+        asset: &Asset,
+        prev_state: &State,
+        target: &TargetConfig,
+        check_local_path: bool,
+    ) -> bool {
+        if force {
+            log::trace!("Asset '{}' will sync (forced)", ident);
+            return true;
+        }
 
-            if let Some(prev) = prev_state.assets.get(ident) {
-                if let Some(prev_state) = prev.targets.get(&target.key) {
-                    // If the hashes differ, sync again
-                    if prev_state.hash != asset.hash {
-                        log::trace!("Asset '{}' has a different hash, will sync", ident);
-                        true
-                    } else {
-						if *check_local_path {
-							if let Some(local_path) = &prev_state.local_path {
-								if !local_path.exists() {
-									log::trace!("Asset '{}' is unchanged but last known path does not exist, will sync", ident);
-									return true
-								}
-							} else {
-								log::trace!("Asset '{}' is unchanged but does not have last known path, will sync", ident);
+        if let Some(prev) = prev_state.assets.get(ident) {
+            if let Some(prev_state) = prev.targets.get(&target.key) {
+                // If the hashes differ, sync again
+                if prev_state.hash != asset.hash {
+                    log::trace!("Asset '{}' has a different hash, will sync", ident);
+                    true
+                } else {
+					if check_local_path {
+						if let Some(local_path) = &prev_state.local_path {
+							if !local_path.exists() {
+								log::trace!("Asset '{}' is unchanged but last known path does not exist, will sync", ident);
 								return true
 							}
+						} else {
+							log::trace!("Asset '{}' is unchanged but does not have last known path, will sync", ident);
+							return true
 						}
+					}
 
-                        log::trace!("Asset '{}' is unchanged, skipping", ident);
-                        false
-                    }
-                } else {
-                    // If we don't have a previous state for this target, sync
-                    log::trace!("Asset '{}' is new for this target, will sync", ident);
-                    true
+                    log::trace!("Asset '{}' is unchanged, skipping", ident);
+                    false
                 }
             } else {
-                // This asset hasn't been uploaded before
-                log::trace!("Asset '{}' is new, will sync", ident);
+                // If we don't have a previous state for this target, sync
+                log::trace!("Asset '{}' is new for this target, will sync", ident);
                 true
             }
-        }))
+        } else {
+            // This asset hasn't been uploaded before
+            log::trace!("Asset '{}' is new, will sync", ident);
+            true
+        }
     }
 
     fn write_state(&self) -> Result<State, SyncError> {
@@ -318,6 +556,8 @@ impl SyncSession {
                         ident.clone(),
                         AssetState {
                             targets: input.targets.clone(),
+                            metadata: input.metadata.clone(),
+                            dpi_scale: input.dpi_scale,
                         },
                     )
                 })
@@ -359,12 +599,8 @@ impl SyncStrategy for LocalSyncStrategy {
 
         // Append the current system time to the filename in Studio's content folder
         // so the new image is always used.
-        let system_time = SystemTime::now();
-        let timestamp = system_time
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            .to_string();
+        let synced_at = unix_time();
+        let timestamp = synced_at.to_string();
 
         let mut base_content_path = PathBuf::from(".runway");
         base_content_path.push(session.config.name.clone());
@@ -403,6 +639,9 @@ impl SyncStrategy for LocalSyncStrategy {
                             replace_slashes(content_path.to_string_lossy().to_string())
                         ),
                         local_path: Some(local_file_path),
+                        synced_at: Some(synced_at),
+                        sprite: None,
+                        unpacked: false,
                     },
                 );
 
@@ -544,6 +783,9 @@ impl SyncStrategy for RobloxSyncStrategy {
                                                 hash: asset.hash.clone(),
                                                 id: format!("rbxassetid://{}", final_id),
                                                 local_path: None,
+                                                synced_at: Some(unix_time()),
+                                                sprite: None,
+                                                unpacked: false,
                                             },
                                         );
 
@@ -609,6 +851,15 @@ async fn roblox_create_asset(
 
     log::trace!("CreateAsset {ident}: sending request");
 
+    // Upload the processed contents rather than the original file
+    let upload_path = std::env::temp_dir().join(format!(
+        "runway-{}-{}{}",
+        asset.hash,
+        ident.last_component(),
+        if ident.is_converted() { ".png" } else { "" }
+    ));
+    fs::write(&upload_path, asset.contents.as_slice())?;
+
     let result = strategy
         .assets
         .create(&CreateAsset {
@@ -621,9 +872,15 @@ async fn roblox_create_asset(
                     expected_price: Some(0),
                 },
             },
-            filepath: asset.path.to_string_lossy().to_string(),
+            filepath: upload_path.to_string_lossy().to_string(),
         })
-        .await?;
+        .await;
+
+    if let Err(e) = fs::remove_file(&upload_path) {
+        log::warn!("CreateAsset {ident}: failed to remove temporary file: {e}");
+    }
+
+    let result = result?;
 
     let operation_path = result.path.ok_or_else(|| SyncError::RobloxApi)?;
 
@@ -688,8 +945,103 @@ async fn get_texture_with_retry(
     Err(SyncError::RobloxApi)
 }
 
-fn generate_asset_hash(content: &[u8]) -> String {
-    format!("{}", blake3::hash(content).to_hex())
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Reads previous target state from file if available, and keeps previous metadata
+// if the file hasn't changed
+fn restore_prev_state(asset: &mut Asset, prev_state: &State) {
+    if let Some(prev) = prev_state.assets.get(&asset.ident) {
+        asset.targets = prev.targets.clone();
+        asset.metadata = prev
+            .metadata
+            .clone()
+            .filter(|metadata| metadata.hash == asset.hash);
+    }
+}
+
+/// Replaces images from inputs with `dpi_scales` with a variant at each scale.
+/// Missing variants are generated by shrinking a higher density file when they're preprocessed.
+pub(crate) fn expand_dpi_variants(assets: &mut BTreeMap<AssetIdent, Asset>, prev_state: &State) {
+    let mut groups: BTreeMap<Vec<u32>, Vec<AssetIdent>> = BTreeMap::new();
+    for (ident, asset) in assets.iter() {
+        if !asset.input.dpi_scales.is_empty() && supports_dpi_scales(ident) {
+            groups
+                .entry(asset.input.dpi_scales.clone())
+                .or_default()
+                .push(ident.clone());
+        }
+    }
+
+    let mut generated = Vec::new();
+
+    for (scales, idents) in groups {
+        for variant in plan_variants(&idents, &scales) {
+            if !variant.is_generated() {
+                assets.get_mut(&variant.ident).unwrap().dpi_scale = Some(variant.scale);
+                continue;
+            }
+
+            let source = &assets[&variant.source];
+
+            log::trace!("Generating '{}' from '{}'", variant.ident, variant.source);
+
+            // Generated variants only change when their source does
+            let hash = blake3::hash(format!("{}@{}x", source.hash, variant.scale).as_bytes())
+                .to_hex()
+                .to_string();
+
+            let mut asset =
+                Asset::new(variant.ident, source.contents.to_vec(), hash, &source.input);
+            asset.dpi_scale = Some(variant.scale);
+            asset.dpi_rescale = Some(variant.scale as f64 / variant.source_scale as f64);
+            restore_prev_state(&mut asset, prev_state);
+
+            generated.push(asset);
+        }
+    }
+
+    // Files at densities that weren't asked for are only used to generate other variants
+    assets.retain(|ident, asset| {
+        let keep = asset.input.dpi_scales.is_empty()
+            || !supports_dpi_scales(ident)
+            || asset.dpi_scale.is_some();
+        if !keep {
+            log::debug!(
+                "Not syncing '{}', which is only used to generate variants",
+                ident
+            );
+        }
+        keep
+    });
+
+    for asset in generated {
+        assets.insert(asset.ident.clone(), asset);
+    }
+}
+
+fn supports_dpi_scales(ident: &AssetIdent) -> bool {
+    matches!(
+        ident.asset_type(),
+        AssetType::DecalPng | AssetType::DecalJpeg
+    )
+}
+
+pub(super) fn generate_asset_hash(content: &[u8], input: &InputConfig) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(content);
+
+    // Changing processing options changes the uploaded file, so include them
+    if let Some(options) = input.processing_key() {
+        hasher.update(b"\0");
+        hasher.update(options.as_bytes());
+    }
+
+    format!("{}", hasher.finalize().to_hex())
 }
 
 #[derive(Error, Debug)]
@@ -7,7 +7,11 @@ use std::{
 use serde::Deserialize;
 use thiserror::Error;
 
-use crate::codegen::CodegenFormat;
+use crate::{
+    asset_ident::AssetKind,
+    codegen::{CodegenFormat, CollisionPolicy, IdFormat, ImageClass, KeyCase, MissingPolicy},
+    preprocess::{PngOptimization, ResizeFilter},
+};
 
 static CONFIG_FILENAME: &str = "runway.toml";
 
@@ -84,7 +88,7 @@ impl From<IntermediateTarget> for TargetConfig {
     }
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, Default)]
 #[serde(deny_unknown_fields)]
 pub struct InputConfig {
     /// A glob matching files containing assets to be upload.
@@ -92,6 +96,130 @@ pub struct InputConfig {
     /// Glob matching uses [`globwalk`](https://docs.rs/globwalk/0.8.1/globwalk/index.html)
     /// which supports [`gitignore`'s glob syntax](https://git-scm.com/docs/gitignore#_pattern_format).
     pub glob: String,
+
+    /// Shrinks images larger than this on either side to fit, keeping their aspect ratio.
+    #[serde(default)]
+    pub max_size: Option<u32>,
+
+    /// The filter used when shrinking images.
+    #[serde(default)]
+    pub resize_filter: ResizeFilter,
+
+    /// Packs matched images into spritesheets instead of uploading them one by one.
+    #[serde(default)]
+    pub pack: bool,
+
+    /// Syncs a variant of each image at these densities, from files named like `icon@2x.png`.
+    /// Missing densities are generated by shrinking a higher density file.
+    #[serde(default)]
+    pub dpi_scales: Vec<u32>,
+
+    /// Reduces the number of colors in PNGs, trading quality for size.
+    #[serde(default)]
+    pub quantize: Option<QuantizeConfig>,
+
+    /// Re-encodes JPEGs at this quality, from 1 to 100.
+    #[serde(default)]
+    pub jpeg_quality: Option<u8>,
+
+    /// Crops fully transparent borders from PNGs.
+    #[serde(default)]
+    pub trim: bool,
+
+    /// Transparent pixels kept around trimmed images so their edges can be alpha bled. Defaults to 1.
+    #[serde(default)]
+    pub trim_padding: Option<u32>,
+
+    /// Removes metadata from PNGs and JPEGs, rotating JPEGs to match their EXIF orientation.
+    #[serde(default)]
+    pub strip_metadata: bool,
+
+    /// Losslessly recompresses PNGs before they're synced.
+    #[serde(default)]
+    pub optimize: Option<PngOptimization>,
+
+    /// Renders SVGs so their larger side is this many pixels.
+    #[serde(default)]
+    pub svg_size: Option<u32>,
+
+    /// Renders SVGs at this multiple of their own size. Defaults to 1.
+    #[serde(default)]
+    pub svg_scale: Option<f32>,
+}
+
+impl InputConfig {
+    /// Describes the processing options that are set, so changing them syncs assets again.
+    /// Returns `None` without any options so existing hashes don't change.
+    pub fn processing_key(&self) -> Option<String> {
+        let mut options = Vec::new();
+
+        if let Some(max_size) = self.max_size {
+            options.push(format!("max_size={max_size}"));
+        }
+
+        // Generated DPI variants are resized with the filter too
+        if self.max_size.is_some() || !self.dpi_scales.is_empty() {
+            options.push(format!("resize_filter={:?}", self.resize_filter));
+        }
+
+        if !self.dpi_scales.is_empty() {
+            options.push(format!("dpi_scales={:?}", self.dpi_scales));
+        }
+
+        if self.pack {
+            options.push("pack".to_string());
+        }
+
+        if let Some(quantize) = &self.quantize {
+            options.push(format!("quantize={},{}", quantize.colors, quantize.dither));
+        }
+
+        if let Some(jpeg_quality) = self.jpeg_quality {
+            options.push(format!("jpeg_quality={jpeg_quality}"));
+        }
+
+        if self.trim {
+            options.push(format!("trim={:?}", self.trim_padding));
+        }
+
+        if self.strip_metadata {
+            options.push("strip_metadata".to_string());
+        }
+
+        if let Some(optimize) = self.optimize {
+            options.push(format!("optimize={optimize:?}"));
+        }
+
+        if let Some(svg_size) = self.svg_size {
+            options.push(format!("svg_size={svg_size}"));
+        }
+
+        if let Some(svg_scale) = self.svg_scale {
+            options.push(format!("svg_scale={svg_scale}"));
+        }
+
+        (!options.is_empty()).then(|| options.join(";"))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QuantizeConfig {
+    /// The most colors an image can have, from 2 to 256.
+    #[serde(default = "default_quantize_colors")]
+    pub colors: u32,
+
+    /// Spreads the difference from the original colors to neighboring pixels to avoid banding.
+    #[serde(default = "default_quantize_dither")]
+    pub dither: bool,
+}
+
+fn default_quantize_colors() -> u32 {
+    256
+}
+
+fn default_quantize_dither() -> bool {
+    true
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -103,6 +231,31 @@ pub struct CodegenConfig {
     /// The format to generate.
     pub format: CodegenFormat,
 
+    /// Generates this output for a specific target instead of the target being synced.
+    #[serde(default)]
+    pub target: Option<String>,
+
+    /// Generates this output for multiple targets, nested under each target's key.
+    /// If `path` contains `{target}`, a separate file is generated for each target instead.
+    #[serde(default)]
+    pub targets: Option<Vec<String>>,
+
+    /// (Template only) The path of the template to render, relative to this config file.
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+
+    /// Only includes assets matching any of these globs, relative to this config file.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Excludes assets matching any of these globs, relative to this config file.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Only includes assets of these types.
+    #[serde(default)]
+    pub asset_types: Option<Vec<AssetKind>>,
+
     /// Removes a path section from paths in the output.
     #[serde(default)]
     pub strip_prefix: Option<PathBuf>,
@@ -114,12 +267,80 @@ pub struct CodegenConfig {
     /// Flattens the output.
     #[serde(default)]
     pub flatten: bool,
+
+    /// Converts each path segment to a case convention.
+    #[serde(default)]
+    pub case: Option<KeyCase>,
+
+    /// Removes whitespace and punctuation from each path segment.
+    #[serde(default)]
+    pub sanitize: bool,
+
+    /// Prepended to path segments that start with a digit.
+    #[serde(default)]
+    pub digit_prefix: Option<String>,
+
+    /// What to do when an asset hasn't been uploaded for the target.
+    #[serde(default)]
+    pub missing: MissingPolicy,
+
+    /// The ID used for missing assets when `missing = "placeholder"`.
+    #[serde(default)]
+    pub placeholder: Option<String>,
+
+    /// The target key to take IDs from for missing assets when `missing = "fallback"`.
+    #[serde(default)]
+    pub fallback: Option<String>,
+
+    /// How asset IDs are written.
+    #[serde(default)]
+    pub id_format: IdFormat,
+
+    /// (Model only) The class of instances generated for images.
+    #[serde(default)]
+    pub image_class: ImageClass,
+
+    /// Generates a record with the asset's ID and metadata instead of only its ID.
+    #[serde(default)]
+    pub metadata: bool,
+
+    /// What to do when multiple assets map to the same key.
+    #[serde(default)]
+    pub on_collision: CollisionPolicy,
+
+    /// (Luau and TypeScript only) Treats `path` as a directory and writes a module for each folder.
+    #[serde(default)]
+    pub split: bool,
+
+    /// (Luau only) Generates a `--!strict` module with an exported type for the output.
+    #[serde(default)]
+    pub typed: bool,
+
+    /// (Luau only) Adds a `get` function that looks up IDs by path.
+    #[serde(default)]
+    pub get_helper: bool,
 }
 
 fn default_strip_extension() -> bool {
     true
 }
 
+const TARGET_PLACEHOLDER: &str = "{target}";
+
+impl CodegenConfig {
+    pub fn path_has_target(&self) -> bool {
+        self.path.to_string_lossy().contains(TARGET_PLACEHOLDER)
+    }
+
+    /// Returns `path` with `{target}` replaced by a target key.
+    pub fn path_for_target(&self, key: &str) -> PathBuf {
+        self.path
+            .to_string_lossy()
+            .replace(TARGET_PLACEHOLDER, key)
+            .into()
+    }
+}
+
 impl Config {
     pub fn read_from_folder_or_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
         let path = path.as_ref();
@@ -157,6 +378,122 @@ impl Config {
         let base_path = path.parent().unwrap();
         for codegen in config.codegens.iter_mut() {
             make_absolute(&mut codegen.path, base_path);
+
+            if let Some(template) = &mut codegen.template {
+                make_absolute(template, base_path);
+            } else if matches!(codegen.format, CodegenFormat::Template) {
+                return Err(ConfigError::MissingTemplate {
+                    path: codegen.path.clone(),
+                });
+            }
+        }
+
+        // Check that codegen outputs use known targets
+        for codegen in &config.codegens {
+            if codegen.target.is_some() && codegen.targets.is_some() {
+                return Err(ConfigError::ConflictingCodegenTargets {
+                    path: codegen.path.clone(),
+                });
+            }
+
+            let can_split = match codegen.format {
+                CodegenFormat::Luau => !codegen.typed && !codegen.get_helper,
+                CodegenFormat::Typescript => true,
+                _ => false,
+            };
+            if codegen.split && !can_split {
+                return Err(ConfigError::UnsupportedSplit {
+                    path: codegen.path.clone(),
+                });
+            }
+
+            if matches!(codegen.format, CodegenFormat::Model) && codegen.id_format != IdFormat::Uri
+            {
+                return Err(ConfigError::UnsupportedModelIds {
+                    path: codegen.path.clone(),
+                });
+            }
+
+            let supports_content = matches!(
+                codegen.format,
+                CodegenFormat::Luau
+                    | CodegenFormat::Typescript
+                    | CodegenFormat::TypescriptDeclaration
+            );
+            if codegen.id_format == IdFormat::Content && !supports_content {
+                return Err(ConfigError::UnsupportedContentIds {
+                    path: codegen.path.clone(),
+                });
+            }
+
+            let missing_option = match codegen.missing {
+                MissingPolicy::Placeholder if codegen.placeholder.is_none() => Some("placeholder"),
+                MissingPolicy::Fallback if codegen.fallback.is_none() => Some("fallback"),
+                _ => None,
+            };
+            if let Some(option) = missing_option {
+                return Err(ConfigError::MissingCodegenOption {
+                    path: codegen.path.clone(),
+                    option,
+                });
+            }
+
+            let keys = codegen
+                .target
+                .iter()
+                .chain(codegen.targets.iter().flatten())
+                .chain(codegen.fallback.iter());
+            for key in keys {
+                if !config.targets.iter().any(|t| t.key == *key) {
+                    return Err(ConfigError::UnknownCodegenTarget { key: key.clone() });
+                }
+            }
+        }
+
+        if let Some(input) = config
+            .inputs
+            .iter()
+            .find(|input| input.dpi_scales.contains(&0))
+        {
+            return Err(ConfigError::InvalidDpiScale {
+                glob: input.glob.clone(),
+            });
+        }
+
+        for input in &config.inputs {
+            if input.max_size == Some(0) {
+                return Err(ConfigError::InvalidMaxSize {
+                    glob: input.glob.clone(),
+                });
+            }
+
+            if input.svg_size.is_some() && input.svg_scale.is_some() {
+                return Err(ConfigError::ConflictingSvgSize {
+                    glob: input.glob.clone(),
+                });
+            }
+
+            if input.svg_size == Some(0) || input.svg_scale.is_some_and(|s| s <= 0.0) {
+                return Err(ConfigError::InvalidSvgSize {
+                    glob: input.glob.clone(),
+                });
+            }
+
+            if input
+                .quantize
+                .as_ref()
+                .is_some_and(|q| !(2..=256).contains(&q.colors))
+            {
+                return Err(ConfigError::InvalidQuantizeColors {
+                    glob: input.glob.clone(),
+                });
+            }
+
+            if input.jpeg_quality.is_some_and(|q| !(1..=100).contains(&q)) {
+                return Err(ConfigError::InvalidJpegQuality {
+                    glob: input.glob.clone(),
+                });
+            }
         }
 
         // Check for duplicate target keys
@@ -193,6 +530,54 @@ pub enum ConfigError {
     #[error("Targets have duplicate keys")]
     DuplicateKeys,
 
+    #[error("Codegen output uses unknown target '{}'", .key)]
+    UnknownCodegenTarget { key: String },
+
+    #[error("Codegen output at {} can't set both `target` and `targets`", .path.display())]
+    ConflictingCodegenTargets { path: PathBuf },
+
+    #[error("Template codegen output at {} requires a template path", .path.display())]
+    MissingTemplate { path: PathBuf },
+
+    #[error("Codegen output at {} requires `{}` to be set", .path.display(), .option)]
+    MissingCodegenOption { path: PathBuf, option: &'static str },
+
+    #[error(
+        "Codegen output at {} can only be split with the luau or typescript formats, without `typed` or `get_helper`",
+        .path.display()
+    )]
+    UnsupportedSplit { path: PathBuf },
+
+    #[error(
+        "Codegen output at {} can only use `id_format = \"content\"` with the luau or typescript formats",
+        .path.display()
+    )]
+    UnsupportedContentIds { path: PathBuf },
+
+    #[error(
+        "Model codegen output at {} can only use `id_format = \"uri\"`",
+        .path.display()
+    )]
+    UnsupportedModelIds { path: PathBuf },
+
+    #[error("Input '{}' has a DPI scale of 0", .glob)]
+    InvalidDpiScale { glob: String },
+
+    #[error("Input '{}' has a max_size of 0", .glob)]
+    InvalidMaxSize { glob: String },
+
+    #[error("Input '{}' sets both svg_size and svg_scale", .glob)]
+    ConflictingSvgSize { glob: String },
+
+    #[error("Input '{}' has an SVG size or scale of 0", .glob)]
+    InvalidSvgSize { glob: String },
+
+    #[error("Input '{}' must quantize to between 2 and 256 colors", .glob)]
+    InvalidQuantizeColors { glob: String },
+
+    #[error("Input '{}' must have a JPEG quality between 1 and 100", .glob)]
+    InvalidJpegQuality { glob: String },
+
     #[error("Error deserializing TOML from path {}", .path.display())]
     Toml {
         path: PathBuf,
@@ -205,3 +590,54 @@ pub enum ConfigError {
         source: io::Error,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read(name: &str, contents: &str) -> Result<Config, ConfigError> {
+        let path = std::env::temp_dir().join(format!("runway-test-{name}.toml"));
+        fs::write(&path, contents).unwrap();
+        let config = Config::read_from_file(&path);
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn max_size() {
+        let config = "name = \"p\"\n[[input]]\nglob = \"*.png\"\nmax_size = ";
+
+        assert!(read("max-size", &(config.to_string() + "64")).is_ok());
+        assert!(matches!(
+            read("max-size-zero", &(config.to_string() + "0")),
+            Err(ConfigError::InvalidMaxSize { .. })
+        ));
+    }
+
+    #[test]
+    fn processing_key() {
+        let key = |input: InputConfig| input.processing_key();
+
+        assert_eq!(key(Default::default()), None);
+        assert_eq!(
+            key(InputConfig {
+                max_size: Some(64),
+                ..Default::default()
+            })
+            .as_deref(),
+            Some("max_size=64;resize_filter=Lanczos3")
+        );
+
+        // Changing the scales or the filter they're resized with changes the key
+        let dpi = |resize_filter| InputConfig {
+            dpi_scales: vec![1, 2],
+            resize_filter,
+            ..Default::default()
+        };
+        assert_eq!(
+            key(dpi(ResizeFilter::Lanczos3)).as_deref(),
+            Some("resize_filter=Lanczos3;dpi_scales=[1, 2]")
+        );
+        assert_ne!(key(dpi(ResizeFilter::Lanczos3)), key(dpi(ResizeFilter::Mitchell)));
+    }
+}
@@ -0,0 +1,189 @@
+//! Groups images named like `icon@2x.png` by density and plans which variants to generate.
+
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::asset_ident::AssetIdent;
+
+static SCALE_SUFFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.*)@(\d+)x(\.[^./]*)?$").unwrap());
+
+/// Splits the density suffix from an ident, e.g. `icon@2x.png` into `icon.png` and 2.
+/// Files without a suffix are 1x.
+pub fn split_scale(ident: &AssetIdent) -> (AssetIdent, u32) {
+    let s = ident.as_ref();
+
+    if let Some(captures) = SCALE_SUFFIX.captures(s) {
+        if let Ok(scale) = captures[2].parse::<u32>() {
+            if scale > 0 {
+                let extension = captures.get(3).map_or("", |m| m.as_str());
+                return (
+                    AssetIdent::generated(&(captures[1].to_string() + extension)),
+                    scale,
+                );
+            }
+        }
+    }
+
+    (ident.clone(), 1)
+}
+
+/// Adds a density suffix to an ident, leaving 1x idents unchanged.
+pub fn with_scale(base: &AssetIdent, scale: u32) -> AssetIdent {
+    if scale == 1 {
+        return base.clone();
+    }
+
+    let s = base.as_ref();
+    let name_start = s.rfind('/').map_or(0, |i| i + 1);
+    let (stem, extension) = match s[name_start..].rfind('.') {
+        Some(i) => s.split_at(name_start + i),
+        None => (s, ""),
+    };
+
+    AssetIdent::generated(&format!("{stem}@{scale}x{extension}"))
+}
+
+/// An image to sync at one density.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub ident: AssetIdent,
+    pub scale: u32,
+
+    /// The file the variant is made from, which is `ident` itself unless it's generated.
+    pub source: AssetIdent,
+    pub source_scale: u32,
+}
+
+impl Variant {
+    pub fn is_generated(&self) -> bool {
+        self.source != self.ident
+    }
+}
+
+/// Chooses a variant for each of `scales` for every group of files with the same base name.
+/// Files that exist at a scale are used directly, and missing scales are generated from
+/// the highest density file. Scales higher than every file are left out.
+/// Files without a suffix are always kept as the 1x variant, even if 1 isn't in `scales`.
+pub fn plan_variants<'a, I>(idents: I, scales: &[u32]) -> Vec<Variant>
+where
+    I: IntoIterator<Item = &'a AssetIdent>,
+{
+    let mut groups: BTreeMap<AssetIdent, BTreeMap<u32, &AssetIdent>> = BTreeMap::new();
+    for ident in idents {
+        let (base, scale) = split_scale(ident);
+        groups.entry(base).or_default().insert(scale, ident);
+    }
+
+    let mut variants = Vec::new();
+
+    for (base, files) in groups {
+        let (&highest, &highest_ident) = files.last_key_value().unwrap();
+
+        let mut scales = scales.to_vec();
+        if files.contains_key(&1) && !scales.contains(&1) {
+            scales.insert(0, 1);
+        }
+
+        for scale in scales {
+            if let Some(&ident) = files.get(&scale) {
+                variants.push(Variant {
+                    ident: ident.clone(),
+                    scale,
+                    source: ident.clone(),
+                    source_scale: scale,
+                });
+            } else if scale < highest {
+                variants.push(Variant {
+                    ident: with_scale(&base, scale),
+                    scale,
+                    source: highest_ident.clone(),
+                    source_scale: highest,
+                });
+            } else {
+                log::debug!(
+                    "Not generating {}@{}x without a higher density file",
+                    base,
+                    scale
+                );
+            }
+        }
+    }
+
+    variants
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ident(s: &str) -> AssetIdent {
+        AssetIdent::generated(s)
+    }
+
+    #[test]
+    fn scales() {
+        assert_eq!(
+            split_scale(&ident("ui/icon@2x.png")),
+            (ident("ui/icon.png"), 2)
+        );
+        assert_eq!(
+            split_scale(&ident("ui/icon.png")),
+            (ident("ui/icon.png"), 1)
+        );
+        assert_eq!(
+            split_scale(&ident("ui/icon@0x.png")),
+            (ident("ui/icon@0x.png"), 1)
+        );
+        assert_eq!(
+            split_scale(&ident("a@2x/icon.png")),
+            (ident("a@2x/icon.png"), 1)
+        );
+
+        assert_eq!(
+            with_scale(&ident("ui/icon.png"), 3),
+            ident("ui/icon@3x.png")
+        );
+        assert_eq!(with_scale(&ident("ui/icon.png"), 1), ident("ui/icon.png"));
+        assert_eq!(with_scale(&ident("a.b/icon"), 2), ident("a.b/icon@2x"));
+    }
+
+    #[test]
+    fn variants() {
+        let files = [
+            ident("close@3x.png"),
+            ident("close@2x.png"),
+            ident("open.png"),
+        ];
+        let variants = plan_variants(&files, &[1, 2, 3]);
+
+        let variant = |name: &str, scale, source: &str, source_scale| Variant {
+            ident: ident(name),
+            scale,
+            source: ident(source),
+            source_scale,
+        };
+
+        assert_eq!(
+            variants,
+            vec![
+                variant("close.png", 1, "close@3x.png", 3),
+                variant("close@2x.png", 2, "close@2x.png", 2),
+                variant("close@3x.png", 3, "close@3x.png", 3),
+                variant("open.png", 1, "open.png", 1),
+            ]
+        );
+
+        // Unsuffixed files are still synced without 1x in the scales
+        let variants = plan_variants(&files, &[2, 3]);
+        assert_eq!(
+            variants,
+            vec![
+                variant("close@2x.png", 2, "close@2x.png", 2),
+                variant("close@3x.png", 3, "close@3x.png", 3),
+                variant("open.png", 1, "open.png", 1),
+            ]
+        );
+    }
+}
@@ -11,6 +11,7 @@ mod cli;
 mod codegen;
 mod commands;
 mod config;
+mod dpi;
 mod preprocess;
 mod state;
 mod symlink;
@@ -44,6 +45,18 @@ async fn main() -> ExitCode {
                 return ExitCode::FAILURE;
             }
         }
+        Subcommand::Check(args) => {
+            if let Err(e) = commands::check(args).await {
+                log::error!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Subcommand::Catalog(args) => {
+            if let Err(e) = commands::catalog(args).await {
+                log::error!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        }
     };
 
     ExitCode::SUCCESS
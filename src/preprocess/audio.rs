@@ -0,0 +1,297 @@
+//! Reads the duration of audio files from their headers and frames without
+//! decoding any audio.
+
+/// Returns the duration in seconds of an Ogg Vorbis or Ogg Opus file.
+pub(crate) fn ogg_duration(data: &[u8]) -> Option<f64> {
+    let mut pos = 0;
+
+    let mut first_packet: Option<&[u8]> = None;
+    let mut last_granule: Option<u64> = None;
+
+    while pos + 27 <= data.len() && &data[pos..pos + 4] == b"OggS" {
+        let granule = u64::from_le_bytes(data[pos + 6..pos + 14].try_into().ok()?);
+        let segment_count = data[pos + 26] as usize;
+
+        let segments_start = pos + 27;
+        let body_start = segments_start + segment_count;
+        let body_len = data
+            .get(segments_start..body_start)?
+            .iter()
+            .map(|s| *s as usize)
+            .sum::<usize>();
+        let body_end = body_start + body_len;
+
+        if first_packet.is_none() {
+            first_packet = Some(data.get(body_start..body_end)?);
+        }
+
+        // A granule position of -1 means no packets finish on this page
+        if granule != u64::MAX {
+            last_granule = Some(granule);
+        }
+
+        pos = body_end;
+    }
+
+    let header = first_packet?;
+    let granule = last_granule?;
+
+    if header.len() >= 16 && &header[0..7] == b"\x01vorbis" {
+        let sample_rate = u32::from_le_bytes(header[12..16].try_into().ok()?);
+        if sample_rate == 0 {
+            return None;
+        }
+
+        Some(granule as f64 / sample_rate as f64)
+    } else if header.len() >= 12 && &header[0..8] == b"OpusHead" {
+        // Opus granule positions are always at 48 kHz and include the pre-skip
+        let pre_skip = u16::from_le_bytes(header[10..12].try_into().ok()?);
+
+        Some(granule.saturating_sub(pre_skip.into()) as f64 / 48000.0)
+    } else {
+        None
+    }
+}
+
+/// Returns the duration in seconds of an MP3 file.
+pub(crate) fn mp3_duration(data: &[u8]) -> Option<f64> {
+    let mut pos = id3v2_len(data);
+
+    let mut first = true;
+    let mut frames = 0_u64;
+    let mut samples = 0_u64;
+    let mut sample_rate = 0;
+
+    while pos + 4 <= data.len() {
+        let Some(frame) = FrameHeader::parse(&data[pos..pos + 4]) else {
+            // Skip junk between frames
+            pos += 1;
+            continue;
+        };
+
+        // A Xing or Info header in the first frame stores the total frame count
+        if first {
+            first = false;
+
+            if let Some(total) = xing_frame_count(&data[pos..], &frame) {
+                return Some(total as f64 * frame.samples as f64 / frame.sample_rate as f64);
+            }
+        }
+
+        frames += 1;
+        samples += frame.samples as u64;
+        sample_rate = frame.sample_rate;
+
+        pos += frame.len;
+    }
+
+    if frames == 0 {
+        return None;
+    }
+
+    Some(samples as f64 / sample_rate as f64)
+}
+
+fn id3v2_len(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[0..3] != b"ID3" {
+        return 0;
+    }
+
+    // The tag size is stored as a 28-bit synchsafe integer
+    let size = data[6..10]
+        .iter()
+        .fold(0_usize, |acc, b| (acc << 7) | (*b & 0x7f) as usize);
+
+    let has_footer = data[5] & 0x10 != 0;
+
+    10 + size + if has_footer { 10 } else { 0 }
+}
+
+#[derive(Debug)]
+struct FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    samples: u32,
+    sample_rate: u32,
+    len: usize,
+}
+
+// Bitrates in kbps indexed by [version group][layer][bitrate index]
+const BITRATES: [[[u32; 15]; 3]; 2] = [
+    // MPEG 1
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+    // MPEG 2 and 2.5
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+
+impl FrameHeader {
+    fn parse(header: &[u8]) -> Option<Self> {
+        // Frames start with 11 set sync bits
+        if header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+
+        let version = (header[1] >> 3) & 0b11;
+        let layer = (header[1] >> 1) & 0b11;
+        let bitrate_index = (header[2] >> 4) as usize;
+        let sample_rate_index = ((header[2] >> 2) & 0b11) as usize;
+        let padding = ((header[2] >> 1) & 1) as usize;
+        let mono = (header[3] >> 6) == 0b11;
+
+        if version == 0b01 || layer == 0b00 || bitrate_index == 0 || bitrate_index == 15 {
+            return None;
+        }
+
+        let sample_rate = match (version, sample_rate_index) {
+            (0b11, 0) => 44100,
+            (0b11, 1) => 48000,
+            (0b11, 2) => 32000,
+            (0b10, 0) => 22050,
+            (0b10, 1) => 24000,
+            (0b10, 2) => 16000,
+            (0b00, 0) => 11025,
+            (0b00, 1) => 12000,
+            (0b00, 2) => 8000,
+            _ => return None,
+        };
+
+        let mpeg1 = version == 0b11;
+
+        // Layer bits are 3 for layer I, 2 for layer II, and 1 for layer III
+        let layer_index = (3 - layer) as usize;
+        let bitrate = BITRATES[if mpeg1 { 0 } else { 1 }][layer_index][bitrate_index] * 1000;
+
+        let (samples, len) = match layer_index {
+            0 => (384, (12 * bitrate / sample_rate) as usize * 4 + padding * 4),
+            1 => (1152, (144 * bitrate / sample_rate) as usize + padding),
+            _ if mpeg1 => (1152, (144 * bitrate / sample_rate) as usize + padding),
+            _ => (576, (72 * bitrate / sample_rate) as usize + padding),
+        };
+
+        Some(Self {
+            mpeg1,
+            mono,
+            samples,
+            sample_rate,
+            len,
+        })
+    }
+}
+
+fn xing_frame_count(frame: &[u8], header: &FrameHeader) -> Option<u32> {
+    // The Xing header comes after the side information
+    let side_info_len = match (header.mpeg1, header.mono) {
+        (true, false) => 32,
+        (true, true) => 17,
+        (false, false) => 17,
+        (false, true) => 9,
+    };
+    let start = 4 + side_info_len;
+
+    let tag = frame.get(start..start + 4)?;
+    if tag != b"Xing" && tag != b"Info" {
+        return None;
+    }
+
+    let flags = u32::from_be_bytes(frame.get(start + 4..start + 8)?.try_into().ok()?);
+    if flags & 1 == 0 {
+        return None;
+    }
+
+    Some(u32::from_be_bytes(
+        frame.get(start + 8..start + 12)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ogg_page(granule: u64, body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend([0, 0]);
+        page.extend(granule.to_le_bytes());
+        page.extend([0; 12]);
+        page.push(1);
+        page.push(body.len() as u8);
+        page.extend(body);
+        page
+    }
+
+    #[test]
+    fn vorbis() {
+        let mut header = b"\x01vorbis".to_vec();
+        header.extend([0, 0, 0, 0, 2]);
+        header.extend(44100_u32.to_le_bytes());
+        header.extend([0; 14]);
+
+        let mut data = ogg_page(0, &header);
+        data.extend(ogg_page(44100, &[0; 10]));
+        data.extend(ogg_page(u64::MAX, &[0; 10]));
+        data.extend(ogg_page(88200, &[0; 10]));
+
+        assert_eq!(ogg_duration(&data), Some(2.0));
+    }
+
+    #[test]
+    fn opus() {
+        let mut header = b"OpusHead".to_vec();
+        header.extend([1, 2]);
+        header.extend(312_u16.to_le_bytes());
+        header.extend([0; 7]);
+
+        let mut data = ogg_page(0, &header);
+        data.extend(ogg_page(48000 + 312, &[0; 10]));
+
+        assert_eq!(ogg_duration(&data), Some(1.0));
+    }
+
+    #[test]
+    fn mp3_frames() {
+        // MPEG 1 layer III, 128 kbps, 44.1 kHz, no padding, joint stereo
+        let mut frame = vec![0xff, 0xfb, 0x90, 0x40];
+        frame.resize(417, 0);
+
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x00\x05".to_vec();
+        data.extend([0; 5]);
+        for _ in 0..10 {
+            data.extend(&frame);
+        }
+
+        assert_eq!(mp3_duration(&data), Some(10.0 * 1152.0 / 44100.0));
+    }
+
+    #[test]
+    fn mp3_xing() {
+        let mut frame = vec![0xff, 0xfb, 0x90, 0x40];
+        frame.resize(4 + 32, 0);
+        frame.extend(b"Xing");
+        frame.extend(1_u32.to_be_bytes());
+        frame.extend(1000_u32.to_be_bytes());
+        frame.resize(417, 0);
+
+        assert_eq!(mp3_duration(&frame), Some(1000.0 * 1152.0 / 44100.0));
+    }
+
+    #[test]
+    fn not_audio() {
+        assert_eq!(ogg_duration(b"not an ogg file"), None);
+        assert_eq!(mp3_duration(b"not an mp3 file"), None);
+    }
+}
@@ -0,0 +1,789 @@
+//! Decodes image formats that Roblox doesn't accept so they can be converted to PNG.
+//!
+//! BMP, TGA, and PSD are read here since only their simplest forms need to be supported:
+//! uncompressed or RLE pixels, and for PSD only the flattened composite image.
+
+use std::io::Cursor;
+
+use super::image::{DecodeError, Image};
+
+// Largest image that will be decoded, so malformed sizes can't allocate huge buffers
+const MAX_PIXELS: u32 = 8192 * 8192;
+
+/// Decodes an image in any supported format, detected from its contents.
+/// TGA files have no signature, so `extension` is used to recognize them.
+pub(super) fn decode_any(contents: &[u8], extension: &str) -> Result<Image, DecodeError> {
+    if contents.starts_with(b"\x89PNG") {
+        Image::decode_png(contents)
+    } else if contents.starts_with(b"\xFF\xD8") {
+        Image::decode_jpeg(contents)
+    } else if contents.starts_with(b"BM") {
+        decode_bmp(contents)
+    } else if contents.starts_with(b"GIF8") {
+        decode_gif(contents)
+    } else if contents.starts_with(b"RIFF") && contents.get(8..12) == Some(b"WEBP") {
+        decode_webp(contents)
+    } else if contents.starts_with(b"8BPS") {
+        decode_psd(contents)
+    } else if extension.eq_ignore_ascii_case("tga") {
+        decode_tga(contents)
+    } else {
+        Err(DecodeError::UnknownFormat)
+    }
+}
+
+// Number of pixels in an image, checking that its size is reasonable
+fn pixel_count(width: u32, height: u32) -> Result<usize, DecodeError> {
+    if width == 0 || height == 0 {
+        return Err(DecodeError::Malformed("invalid size"));
+    }
+
+    width
+        .checked_mul(height)
+        .filter(|&count| count <= MAX_PIXELS)
+        .map(|count| count as usize)
+        .ok_or(DecodeError::Malformed("image is too large"))
+}
+
+fn bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], DecodeError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(DecodeError::Malformed("unexpected end of file"))
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Result<u16, DecodeError> {
+    Ok(u16::from_le_bytes(
+        bytes(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(
+        bytes(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Result<u16, DecodeError> {
+    Ok(u16::from_be_bytes(
+        bytes(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    Ok(u32::from_be_bytes(
+        bytes(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+// Extracts a channel from a packed pixel and scales it to 8 bits
+fn channel(value: u32, mask: u32) -> Option<u8> {
+    if mask == 0 {
+        return None;
+    }
+
+    let max = mask >> mask.trailing_zeros();
+    let v = (value & mask) >> mask.trailing_zeros();
+    Some(((v as u64 * 255 + max as u64 / 2) / max as u64) as u8)
+}
+
+fn decode_bmp(data: &[u8]) -> Result<Image, DecodeError> {
+    let pixel_offset = le_u32(data, 10)? as usize;
+    let header_size = le_u32(data, 14)? as usize;
+    if header_size < 40 {
+        return Err(DecodeError::Unsupported("BMP files with an OS/2 header"));
+    }
+
+    let width = le_u32(data, 18)? as i32;
+    let height = le_u32(data, 22)? as i32;
+    let bpp = le_u16(data, 28)? as u32;
+    let compression = le_u32(data, 30)?;
+    let colors_used = le_u32(data, 46)?;
+
+    if width <= 0 || height == 0 {
+        return Err(DecodeError::Malformed("invalid size"));
+    }
+
+    // Rows are stored bottom to top unless the height is negative
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    let pixel_count = pixel_count(width, height)?;
+
+    // Masks for the red, green, blue, and alpha channels of 16 and 32 bit pixels
+    let masks = match (compression, bpp) {
+        (0, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (0, 32) => [0xff0000, 0xff00, 0xff, 0xff000000],
+        (3 | 6, 16 | 32) => {
+            let has_alpha = compression == 6 || header_size >= 56;
+            [
+                le_u32(data, 54)?,
+                le_u32(data, 58)?,
+                le_u32(data, 62)?,
+                if has_alpha { le_u32(data, 66)? } else { 0 },
+            ]
+        }
+        (0, 1 | 4 | 8 | 24) => [0; 4],
+        _ => return Err(DecodeError::Unsupported("compressed BMP files")),
+    };
+
+    let palette = if bpp <= 8 {
+        let count = if colors_used > 0 {
+            colors_used
+        } else {
+            1 << bpp
+        };
+        let palette = bytes(data, 14 + header_size, count as usize * 4)?;
+        palette
+            .chunks_exact(4)
+            .map(|c| [c[2], c[1], c[0], 255])
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+
+    // Rows are padded to 4 bytes
+    let stride = ((bpp * width).div_ceil(32) * 4) as usize;
+
+    // Check the pixels are all there before allocating for them
+    bytes(data, pixel_offset, stride * height as usize)?;
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for y in 0..height {
+        let row_index = if top_down { y } else { height - 1 - y };
+        let row = bytes(data, pixel_offset + row_index as usize * stride, stride)?;
+
+        for x in 0..width as usize {
+            let pixel = match bpp {
+                1 | 4 | 8 => {
+                    let bit = x * bpp as usize;
+                    let shift = 8 - bpp as usize - bit % 8;
+                    let index = (row[bit / 8] >> shift) as usize & ((1 << bpp) - 1);
+                    *palette
+                        .get(index)
+                        .ok_or(DecodeError::Malformed("palette index out of range"))?
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => {
+                    let value = if bpp == 16 {
+                        u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes(row[x * 4..x * 4 + 4].try_into().unwrap())
+                    };
+                    [
+                        channel(value, masks[0]).unwrap_or(0),
+                        channel(value, masks[1]).unwrap_or(0),
+                        channel(value, masks[2]).unwrap_or(0),
+                        channel(value, masks[3]).unwrap_or(255),
+                    ]
+                }
+            };
+            rgba.extend(pixel);
+        }
+    }
+
+    // Uncompressed 32 bit files often leave the alpha channel empty
+    if compression == 0 && bpp == 32 && rgba.chunks_exact(4).all(|p| p[3] == 0) {
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+    }
+
+    Ok(Image::new_rgba8((width, height), rgba))
+}
+
+fn decode_tga(data: &[u8]) -> Result<Image, DecodeError> {
+    let header = bytes(data, 0, 18)?;
+    let id_length = header[0] as usize;
+    let has_color_map = header[1] == 1;
+    let image_type = header[2];
+    let color_map_first = le_u16(header, 3)? as usize;
+    let color_map_length = le_u16(header, 5)? as usize;
+    let color_map_depth = header[7];
+    let width = le_u16(header, 12)? as u32;
+    let height = le_u16(header, 14)? as u32;
+    let depth = header[16];
+    let descriptor = header[17];
+
+    // Image types 9 to 11 are the run-length encoded versions of 1 to 3
+    let rle = image_type & 8 != 0;
+    let kind = image_type & !8;
+    if !matches!(kind, 1..=3) {
+        return Err(DecodeError::Unsupported("TGA files without image data"));
+    }
+    if !(1..=32).contains(&depth) || (has_color_map && !(1..=32).contains(&color_map_depth)) {
+        return Err(DecodeError::Malformed("invalid pixel depth"));
+    }
+
+    let has_alpha = depth == 32 && descriptor & 0x0f != 0;
+    let color = |entry: &[u8], depth: u8| -> Result<[u8; 4], DecodeError> {
+        Ok(match depth {
+            15 | 16 => {
+                let v = u16::from_le_bytes([entry[0], entry[1]]) as u32;
+                [
+                    channel(v, 0x7c00).unwrap(),
+                    channel(v, 0x03e0).unwrap(),
+                    channel(v, 0x001f).unwrap(),
+                    255,
+                ]
+            }
+            24 => [entry[2], entry[1], entry[0], 255],
+            32 => [entry[2], entry[1], entry[0], entry[3]],
+            _ => return Err(DecodeError::Unsupported("TGA pixel depth")),
+        })
+    };
+
+    let mut offset = 18 + id_length;
+
+    let mut color_map = Vec::new();
+    if has_color_map {
+        let entry_size = (color_map_depth as usize).div_ceil(8);
+        let entries = bytes(data, offset, color_map_length * entry_size)?;
+        for entry in entries.chunks_exact(entry_size) {
+            color_map.push(color(entry, color_map_depth)?);
+        }
+        offset += color_map_length * entry_size;
+    }
+
+    // Read every pixel's raw bytes, expanding runs
+    let pixel_size = (depth as usize).div_ceil(8);
+    let pixel_count = pixel_count(width, height)?;
+    let raw = if rle {
+        // Runs can claim any size, so the buffer only grows as they're read
+        let mut raw = Vec::new();
+        while raw.len() < pixel_count * pixel_size {
+            let packet = *bytes(data, offset, 1)?.first().unwrap();
+            let count = (packet & 0x7f) as usize + 1;
+            offset += 1;
+
+            if packet & 0x80 != 0 {
+                let pixel = bytes(data, offset, pixel_size)?;
+                for _ in 0..count {
+                    raw.extend_from_slice(pixel);
+                }
+                offset += pixel_size;
+            } else {
+                raw.extend_from_slice(bytes(data, offset, count * pixel_size)?);
+                offset += count * pixel_size;
+            }
+        }
+        raw.truncate(pixel_count * pixel_size);
+        raw
+    } else {
+        bytes(data, offset, pixel_count * pixel_size)?.to_vec()
+    };
+
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for entry in raw.chunks_exact(pixel_size) {
+        let mut pixel = match kind {
+            1 => {
+                let index = match pixel_size {
+                    1 => entry[0] as usize,
+                    _ => u16::from_le_bytes([entry[0], entry[1]]) as usize,
+                };
+                *index
+                    .checked_sub(color_map_first)
+                    .and_then(|i| color_map.get(i))
+                    .ok_or(DecodeError::Malformed("color map index out of range"))?
+            }
+            2 => color(entry, depth)?,
+            _ => {
+                let alpha = if pixel_size > 1 { entry[1] } else { 255 };
+                [entry[0], entry[0], entry[0], alpha]
+            }
+        };
+        if kind == 2 && !has_alpha {
+            pixel[3] = 255;
+        }
+        pixels.push(pixel);
+    }
+
+    // Rows are stored bottom to top and left to right unless the descriptor says otherwise
+    let top_down = descriptor & 0x20 != 0;
+    let right_to_left = descriptor & 0x10 != 0;
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+        for x in 0..width {
+            let column = if right_to_left { width - 1 - x } else { x };
+            rgba.extend(pixels[(row * width + column) as usize]);
+        }
+    }
+
+    Ok(Image::new_rgba8((width, height), rgba))
+}
+
+fn decode_gif(data: &[u8]) -> Result<Image, DecodeError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data)?;
+
+    let size = (decoder.width() as u32, decoder.height() as u32);
+    let pixel_count = pixel_count(size.0, size.1)?;
+    let frame = decoder
+        .read_next_frame()?
+        .ok_or(DecodeError::Malformed("GIF has no frames"))?;
+
+    // The first frame can cover only part of the image
+    let mut rgba = vec![0; pixel_count * 4];
+    for y in 0..frame.height as u32 {
+        for x in 0..frame.width as u32 {
+            let (image_x, image_y) = (frame.left as u32 + x, frame.top as u32 + y);
+            if image_x >= size.0 || image_y >= size.1 {
+                continue;
+            }
+
+            let from = ((y * frame.width as u32 + x) * 4) as usize;
+            let to = ((image_y * size.0 + image_x) * 4) as usize;
+            rgba[to..to + 4].copy_from_slice(&frame.buffer[from..from + 4]);
+        }
+    }
+
+    Ok(Image::new_rgba8(size, rgba))
+}
+
+fn decode_webp(data: &[u8]) -> Result<Image, DecodeError> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(data))?;
+
+    let size = decoder.dimensions();
+    pixel_count(size.0, size.1)?;
+    let size_bytes = decoder
+        .output_buffer_size()
+        .ok_or(DecodeError::Malformed("image is too large"))?;
+
+    // Animated images decode their first frame
+    let mut buffer = vec![0; size_bytes];
+    decoder.read_image(&mut buffer)?;
+
+    let rgba = if decoder.has_alpha() {
+        buffer
+    } else {
+        buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect()
+    };
+
+    Ok(Image::new_rgba8(size, rgba))
+}
+
+// Expands PackBits run-length encoding
+fn unpack_bits(mut input: &[u8], output: &mut Vec<u8>, len: usize) -> Result<(), DecodeError> {
+    let end = output.len() + len;
+
+    while output.len() < end {
+        let (&header, rest) = input
+            .split_first()
+            .ok_or(DecodeError::Malformed("unexpected end of row"))?;
+        input = rest;
+
+        match header as i8 {
+            -128 => {}
+            n if n < 0 => {
+                let (&value, rest) = input
+                    .split_first()
+                    .ok_or(DecodeError::Malformed("unexpected end of row"))?;
+                input = rest;
+                output.extend(std::iter::repeat_n(value, (1 - n as isize) as usize));
+            }
+            n => {
+                let count = n as usize + 1;
+                output.extend_from_slice(bytes(input, 0, count)?);
+                input = &input[count..];
+            }
+        }
+    }
+
+    output.truncate(end);
+    Ok(())
+}
+
+fn decode_psd(data: &[u8]) -> Result<Image, DecodeError> {
+    if be_u16(data, 4)? != 1 {
+        return Err(DecodeError::Unsupported("large document (PSB) files"));
+    }
+
+    let channels = be_u16(data, 12)? as usize;
+    let height = be_u32(data, 14)?;
+    let width = be_u32(data, 18)?;
+    let depth = be_u16(data, 22)?;
+    let color_mode = be_u16(data, 24)?;
+
+    if depth != 8 && depth != 16 {
+        return Err(DecodeError::Unsupported(
+            "PSD bit depths other than 8 or 16",
+        ));
+    }
+
+    // Grayscale or RGB, followed by the composite's transparency if it has any
+    let color_channels = match color_mode {
+        1 => 1,
+        3 => 3,
+        _ => {
+            return Err(DecodeError::Unsupported(
+                "PSD color modes other than RGB or grayscale",
+            ))
+        }
+    };
+    if channels < color_channels {
+        return Err(DecodeError::Malformed("missing color channels"));
+    }
+    let used_channels = channels.min(color_channels + 1);
+
+    // Skip the color mode data, image resources, and layers to get to the flattened image
+    let mut offset = 26;
+    for _ in 0..3 {
+        offset += 4 + be_u32(data, offset)? as usize;
+    }
+
+    let compression = be_u16(data, offset)?;
+    offset += 2;
+
+    let pixel_count = pixel_count(width, height)?;
+    let sample_size = depth as usize / 8;
+    let row_len = width as usize * sample_size;
+    let plane_len = pixel_count * sample_size;
+
+    // Runs can claim any size, so the buffer only grows as they're read
+    let mut planes = Vec::new();
+    match compression {
+        0 => planes.extend_from_slice(bytes(data, offset, plane_len * used_channels)?),
+        1 => {
+            // Every row of every channel is compressed separately, after a table of their lengths
+            let rows = channels * height as usize;
+            let mut row_offset = offset + rows * 2;
+
+            for row in 0..used_channels * height as usize {
+                let len = be_u16(data, offset + row * 2)? as usize;
+                unpack_bits(bytes(data, row_offset, len)?, &mut planes, row_len)?;
+                row_offset += len;
+            }
+        }
+        _ => return Err(DecodeError::Unsupported("PSD files with ZIP compression")),
+    }
+
+    // 16 bit samples are big endian, so their first byte is the most significant
+    let sample = |channel: usize, i: usize| planes[channel * plane_len + i * sample_size];
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for i in 0..pixel_count {
+        let alpha = if used_channels > color_channels {
+            sample(color_channels, i)
+        } else {
+            255
+        };
+
+        if color_channels == 3 {
+            rgba.extend([sample(0, i), sample(1, i), sample(2, i), alpha]);
+        } else {
+            rgba.extend([sample(0, i), sample(0, i), sample(0, i), alpha]);
+        }
+    }
+
+    Ok(Image::new_rgba8((width, height), rgba))
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    // An uncompressed BMP with a 40 byte header, followed directly by `pixels`
+    fn bmp(width: i32, height: i32, bpp: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut data = b"BM".to_vec();
+        data.extend((54 + pixels.len() as u32).to_le_bytes());
+        data.extend([0; 4]);
+        data.extend(54_u32.to_le_bytes());
+        data.extend(40_u32.to_le_bytes());
+        data.extend(width.to_le_bytes());
+        data.extend(height.to_le_bytes());
+        data.extend(1_u16.to_le_bytes());
+        data.extend(bpp.to_le_bytes());
+        data.extend([0; 24]);
+        data.extend(pixels);
+        data
+    }
+
+    // A TGA without an ID or color map, followed directly by `pixels`
+    fn tga(image_type: u8, size: (u16, u16), depth: u8, descriptor: u8, pixels: &[u8]) -> Vec<u8> {
+        let mut data = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.extend(size.0.to_le_bytes());
+        data.extend(size.1.to_le_bytes());
+        data.extend([depth, descriptor]);
+        data.extend(pixels);
+        data
+    }
+
+    // An 8 bit RGB PSD without color mode data, image resources, or layers
+    fn psd(channels: u16, size: (u32, u32), compression: u16, image_data: &[u8]) -> Vec<u8> {
+        let mut data = b"8BPS".to_vec();
+        data.extend(1_u16.to_be_bytes());
+        data.extend([0; 6]);
+        data.extend(channels.to_be_bytes());
+        data.extend(size.1.to_be_bytes());
+        data.extend(size.0.to_be_bytes());
+        data.extend(8_u16.to_be_bytes());
+        data.extend(3_u16.to_be_bytes());
+        data.extend([0; 12]);
+        data.extend(compression.to_be_bytes());
+        data.extend(image_data);
+        data
+    }
+
+    fn gif() -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut data, 2, 2, &[255, 0, 0, 0, 0, 255]).unwrap();
+        encoder
+            .write_frame(&gif::Frame {
+                width: 2,
+                height: 2,
+                buffer: Cow::Borrowed(&[0, 1, 1, 0]),
+                ..Default::default()
+            })
+            .unwrap();
+        drop(encoder);
+        data
+    }
+
+    fn webp() -> Vec<u8> {
+        let mut data = Vec::new();
+        image_webp::WebPEncoder::new(&mut data)
+            .encode(
+                &[255, 0, 0, 255].repeat(4),
+                2,
+                2,
+                image_webp::ColorType::Rgba8,
+            )
+            .unwrap();
+        data
+    }
+
+    fn is_malformed(result: Result<Image, DecodeError>) -> bool {
+        matches!(result, Err(DecodeError::Malformed(_)))
+    }
+
+    #[test]
+    fn bmp_24_bit() {
+        // 2x2, bottom to top, with rows padded from 6 to 8 bytes
+        let mut pixels = vec![0, 0, 255, 0, 255, 0, 0, 0];
+        pixels.extend([255, 0, 0, 255, 255, 255, 0, 0]);
+        let data = bmp(2, 2, 24, &pixels);
+
+        let image = decode_any(&data, "bmp").unwrap();
+        assert_eq!(image.size(), (2, 2));
+        assert_eq!(image.get_pixel((0, 0)), Pixel::new(0, 0, 255, 255));
+        assert_eq!(image.get_pixel((1, 0)), Pixel::new(255, 255, 255, 255));
+        assert_eq!(image.get_pixel((0, 1)), Pixel::new(255, 0, 0, 255));
+        assert_eq!(image.get_pixel((1, 1)), Pixel::new(0, 255, 0, 255));
+    }
+
+    #[test]
+    fn tga_rle() {
+        // 3x1, 32 bit with 8 alpha bits, top to bottom.
+        // A run of two red pixels, then one raw half transparent blue pixel
+        let data = tga(
+            10,
+            (3, 1),
+            32,
+            0x28,
+            &[0x81, 0, 0, 255, 255, 0x00, 255, 0, 0, 128],
+        );
+
+        let image = decode_any(&data, "TGA").unwrap();
+        assert_eq!(image.size(), (3, 1));
+        assert_eq!(image.get_pixel((0, 0)), Pixel::new(255, 0, 0, 255));
+        assert_eq!(image.get_pixel((1, 0)), Pixel::new(255, 0, 0, 255));
+        assert_eq!(image.get_pixel((2, 0)), Pixel::new(0, 0, 255, 128));
+    }
+
+    #[test]
+    fn psd_rle() {
+        // 2x1 RGBA
+        let mut image_data = [2, 3, 2, 2]
+            .iter()
+            .flat_map(|n: &u16| n.to_be_bytes())
+            .collect::<Vec<_>>();
+        // Red is a run, green is literal, blue is a run, and alpha is a run
+        image_data.extend([0xff, 200]);
+        image_data.extend([0x01, 10, 20]);
+        image_data.extend([0xff, 0]);
+        image_data.extend([0xff, 255]);
+        let data = psd(4, (2, 1), 1, &image_data);
+
+        let image = decode_any(&data, "psd").unwrap();
+        assert_eq!(image.size(), (2, 1));
+        assert_eq!(image.get_pixel((0, 0)), Pixel::new(200, 10, 0, 255));
+        assert_eq!(image.get_pixel((1, 0)), Pixel::new(200, 20, 0, 255));
+    }
+
+    #[test]
+    fn truncated() {
+        let files = [
+            (bmp(2, 2, 24, &[0; 16]), "bmp"),
+            (bmp(1, -1, 32, &[0; 4]), "bmp"),
+            (tga(2, (2, 1), 24, 0, &[0; 6]), "tga"),
+            (
+                tga(10, (3, 1), 32, 0, &[0x81, 0, 0, 0, 0, 0x00, 0, 0, 0, 0]),
+                "tga",
+            ),
+            (psd(3, (2, 1), 0, &[0; 6]), "psd"),
+            (
+                psd(3, (2, 1), 1, &[0, 2, 0, 2, 0, 2, 0xff, 1, 0xff, 2, 0xff, 3]),
+                "psd",
+            ),
+        ];
+
+        for (data, extension) in files {
+            assert!(decode_any(&data, extension).is_ok());
+
+            // Every byte is needed, so any shorter file fails without panicking
+            for len in 0..data.len() {
+                assert!(
+                    decode_any(&data[..len], extension).is_err(),
+                    "{extension} file cut to {len} bytes was decoded"
+                );
+            }
+        }
+
+        // Files read with other crates can have trailing data that isn't needed
+        for (data, extension) in [(gif(), "gif"), (webp(), "webp")] {
+            assert_eq!(decode_any(&data, extension).unwrap().size(), (2, 2));
+
+            for len in 0..data.len() {
+                if let Ok(image) = decode_any(&data[..len], extension) {
+                    assert_eq!(image.size(), (2, 2));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oversized() {
+        // Larger than the limit
+        assert!(is_malformed(decode_any(&bmp(65536, 65536, 24, &[]), "bmp")));
+        assert!(is_malformed(decode_any(
+            &tga(2, (65535, 65535), 24, 0, &[]),
+            "tga"
+        )));
+        assert!(is_malformed(decode_any(
+            &psd(3, (30000, 30000), 0, &[]),
+            "psd"
+        )));
+
+        // Within the limit, but without the pixels to fill it
+        assert!(is_malformed(decode_any(
+            &bmp(8192, 8192, 32, &[0; 64]),
+            "bmp"
+        )));
+        assert!(is_malformed(decode_any(
+            &psd(3, (8192, 8192), 0, &[0; 64]),
+            "psd"
+        )));
+
+        // A few runs can't claim more pixels than the file has packets for
+        let runs = [0xff, 0, 0, 0].repeat(4);
+        assert!(is_malformed(decode_any(
+            &tga(10, (8192, 8192), 24, 0, &runs),
+            "tga"
+        )));
+
+        // Headers read by other crates are checked against the same limit
+        let mut data = gif();
+        data[6..10].copy_from_slice(&[0xff; 4]);
+        assert!(decode_any(&data, "gif").is_err());
+
+        let mut data = b"RIFF".to_vec();
+        data.extend(26_u32.to_le_bytes());
+        data.extend(b"WEBPVP8L");
+        data.extend(5_u32.to_le_bytes());
+        // Signature, then the width and height minus one in 14 bits each
+        let size = 16383 | (16383 << 14);
+        data.push(0x2f);
+        data.extend((size as u32).to_le_bytes());
+        data.extend([0; 13]);
+        assert!(is_malformed(decode_any(&data, "webp")));
+    }
+
+    #[test]
+    fn rle_overruns() {
+        // A run of 128 pixels in a 3 pixel image only fills the image
+        let data = tga(10, (3, 1), 24, 0, &[0xff, 0, 0, 255]);
+        let image = decode_any(&data, "tga").unwrap();
+        assert_eq!(image.size(), (3, 1));
+        assert_eq!(image.get_pixel((2, 0)), Pixel::new(255, 0, 0, 255));
+
+        // A raw packet with fewer pixels than it claims
+        let data = tga(10, (3, 1), 24, 0, &[0x7f, 0, 0, 255]);
+        assert!(is_malformed(decode_any(&data, "tga")));
+
+        // Runs longer than a row are cut off at the end of the row
+        let data = psd(3, (2, 1), 1, &[0, 2, 0, 2, 0, 2, 0x81, 1, 0x81, 2, 0x81, 3]);
+        let image = decode_any(&data, "psd").unwrap();
+        assert_eq!(image.size(), (2, 1));
+        assert_eq!(image.get_pixel((1, 0)), Pixel::new(1, 2, 3, 255));
+
+        // A literal longer than the row's data
+        let data = psd(3, (2, 1), 1, &[0, 2, 0, 2, 0, 2, 0x7f, 1, 0xff, 2, 0xff, 3]);
+        assert!(is_malformed(decode_any(&data, "psd")));
+
+        // A row length past the end of the file
+        let data = psd(
+            3,
+            (2, 1),
+            1,
+            &[0, 2, 0, 2, 0xff, 0xff, 0xff, 1, 0xff, 2, 0xff, 3],
+        );
+        assert!(is_malformed(decode_any(&data, "psd")));
+    }
+
+    #[test]
+    fn malformed() {
+        // TGA with a pixel depth of 0
+        assert!(is_malformed(decode_any(&tga(2, (1, 1), 0, 0, &[]), "tga")));
+
+        // TGA with no pixels
+        assert!(is_malformed(decode_any(&tga(2, (0, 1), 24, 0, &[]), "tga")));
+
+        // TGA with a color map index past the end of the map
+        let mut data = tga(1, (1, 1), 8, 0, &[0, 0, 255, 3]);
+        data[1] = 1;
+        data[5..7].copy_from_slice(&1_u16.to_le_bytes());
+        data[7] = 24;
+        assert!(is_malformed(decode_any(&data, "tga")));
+
+        // BMP with a negative width
+        assert!(is_malformed(decode_any(&bmp(-1, 1, 24, &[0; 4]), "bmp")));
+
+        // BMP with a palette index past the end of the palette
+        let mut data = bmp(1, 1, 8, &[0, 0, 255, 0, 5, 0, 0, 0]);
+        data[10..14].copy_from_slice(&58_u32.to_le_bytes());
+        data[46..50].copy_from_slice(&1_u32.to_le_bytes());
+        assert!(is_malformed(decode_any(&data, "bmp")));
+
+        // BMP with a header too small to be a bitmap info header
+        let mut data = bmp(1, 1, 24, &[0; 4]);
+        data[14..18].copy_from_slice(&12_u32.to_le_bytes());
+        assert!(matches!(
+            decode_any(&data, "bmp"),
+            Err(DecodeError::Unsupported(_))
+        ));
+
+        // PSD with fewer channels than its color mode needs
+        assert!(is_malformed(decode_any(&psd(2, (1, 1), 0, &[0; 2]), "psd")));
+
+        // PSD with a section longer than the file
+        let mut data = psd(3, (1, 1), 0, &[0; 3]);
+        data[26..30].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(is_malformed(decode_any(&data, "psd")));
+    }
+
+    #[test]
+    fn unknown() {
+        assert!(matches!(
+            decode_any(b"not an image", "png"),
+            Err(DecodeError::UnknownFormat)
+        ));
+    }
+}
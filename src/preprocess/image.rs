@@ -1,6 +1,9 @@
 //! Simple containers to track images and perform operations on them.
 
-use std::io::{Read, Write};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
 
 use thiserror::Error;
 
@@ -54,7 +57,11 @@ impl Image {
     }
 
     pub fn decode_png<R: Read>(input: R) -> Result<Self, DecodeError> {
-        let decoder = png::Decoder::new(input);
+        let mut decoder = png::Decoder::new(input);
+
+        // Expand palettes, low bit depths, and tRNS transparency, and strip 16-bit
+        // channels so every image is 8 bits per channel
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
 
         // Get the metadata we need from the image and read its data into a
         // buffer for processing by the sprite packing algorithm
@@ -62,39 +69,54 @@ impl Image {
         let mut data = vec![0; reader.output_buffer_size()];
 
         let info = reader.next_frame(&mut data)?;
-
-        // TODO: Transcode images to RGBA if possible
-        match info.color_type {
-            png::ColorType::Rgba => {}
-            png::ColorType::Rgb => {
-                let mut iter = data.iter().peekable();
-                let mut count = 0;
-                data = std::iter::from_fn(|| match iter.peek() {
-                    Some(_) => {
-                        if count < 3 {
-                            count += 1;
-                            iter.next()
-                        } else {
-                            count = 0;
-                            Some(&0_u8)
-                        }
-                    }
-                    None => None,
-                })
-                .cloned()
-                .collect();
-                data.push(0);
-            }
-            _ => {
-                return Err(DecodeError::ColorType(info.color_type));
+        data.truncate(info.buffer_size());
+
+        let (color_type, _) = reader.output_color_type();
+
+        data = match color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|l| [*l, *l, *l, 255]).collect(),
+            png::ColorType::Indexed => {
+                return Err(DecodeError::ColorType(color_type));
             }
-        }
+        };
 
         let size = (info.width, info.height);
 
         Ok(Self::new_rgba8(size, data))
     }
 
+    pub fn decode_jpeg<R: Read>(input: R) -> Result<Self, DecodeError> {
+        let mut decoder = jpeg_decoder::Decoder::new(input);
+        let pixels = decoder.decode()?;
+
+        // Checked by decode
+        let info = decoder.info().unwrap();
+
+        let data: Vec<u8> = match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 => {
+                pixels.iter().flat_map(|l| [*l, *l, *l, 255]).collect()
+            }
+            jpeg_decoder::PixelFormat::RGB24 => pixels
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            format => return Err(DecodeError::PixelFormat(format)),
+        };
+
+        let size = (info.width.into(), info.height.into());
+
+        Ok(Self::new_rgba8(size, data))
+    }
+
     pub fn encode_png<W: Write>(&self, output: W) -> Result<(), png::EncodingError> {
         let mut encoder = png::Encoder::new(output, self.size.0, self.size.1);
 
@@ -112,10 +134,104 @@ impl Image {
         Ok(())
     }
 
+    /// Encodes the image as an indexed PNG if it has at most 256 colors, with a `tRNS` chunk
+    /// for any transparency. Returns `false` without writing anything if it has more.
+    pub fn encode_indexed_png<W: Write>(&self, output: W) -> Result<bool, png::EncodingError> {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        for p in self.data.chunks_exact(4) {
+            if !lookup.contains_key(p) {
+                if palette.len() == 256 {
+                    return Ok(false);
+                }
+                lookup.insert(p, 0);
+                palette.push([p[0], p[1], p[2], p[3]]);
+            }
+        }
+
+        // Put transparent colors first so opaque ones can be left out of the tRNS chunk
+        palette.sort_by_key(|c| (c[3] == 255, *c));
+        for (i, c) in palette.iter().enumerate() {
+            *lookup.get_mut(c.as_slice()).unwrap() = i as u8;
+        }
+
+        let mut encoder = png::Encoder::new(output, self.size.0, self.size.1);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|c| [c[0], c[1], c[2]])
+                .collect::<Vec<_>>(),
+        );
+
+        let transparent = palette.iter().take_while(|c| c[3] != 255).count();
+        if transparent > 0 {
+            encoder.set_trns(
+                palette[..transparent]
+                    .iter()
+                    .map(|c| c[3])
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let indices = self
+            .data
+            .chunks_exact(4)
+            .map(|p| lookup[p])
+            .collect::<Vec<_>>();
+
+        let mut output_writer = encoder.write_header()?;
+        output_writer.write_image_data(&indices)?;
+
+        Ok(true)
+    }
+
+    /// Encodes the image as a baseline JPEG, discarding alpha.
+    pub fn encode_jpeg(
+        &self,
+        output: &mut Vec<u8>,
+        quality: u8,
+    ) -> Result<(), jpeg_encoder::EncodingError> {
+        let encoder = jpeg_encoder::Encoder::new(output, quality);
+        encoder.encode(
+            &self.data,
+            self.size.0 as u16,
+            self.size.1 as u16,
+            jpeg_encoder::ColorType::Rgba,
+        )
+    }
+
     pub fn size(&self) -> (u32, u32) {
         self.size
     }
 
+    /// Returns the raw RGBA8 pixel data, row by row.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns whether every pixel is fully opaque.
+    pub fn is_opaque(&self) -> bool {
+        self.data.chunks_exact(4).all(|p| p[3] == 255)
+    }
+
+    /// Copies the area of the image starting at `pos` with the given size.
+    pub fn crop(&self, pos: (u32, u32), size: (u32, u32)) -> Image {
+        assert!(pos.0 + size.0 <= self.size.0);
+        assert!(pos.1 + size.1 <= self.size.1);
+
+        let stride = self.format.stride() as usize;
+        let mut data = Vec::with_capacity(size.0 as usize * size.1 as usize * stride);
+
+        for y in pos.1..pos.1 + size.1 {
+            let start = stride * (pos.0 + y * self.size.0) as usize;
+            data.extend_from_slice(&self.data[start..start + stride * size.0 as usize]);
+        }
+
+        Image::new_rgba8(size, data)
+    }
+
     pub fn blit(&mut self, other: &Image, pos: (u32, u32)) {
         assert!(self.format == ImageFormat::Rgba8 && other.format == ImageFormat::Rgba8);
 
@@ -171,11 +287,47 @@ pub enum DecodeError {
     #[error("Color format {0:?} is not supported by Runway.")]
     ColorType(png::ColorType),
 
+    #[error("Pixel format {0:?} is not supported by Runway.")]
+    PixelFormat(jpeg_decoder::PixelFormat),
+
     #[error(transparent)]
     Png {
         #[from]
         source: png::DecodingError,
     },
+
+    #[error(transparent)]
+    Jpeg {
+        #[from]
+        source: jpeg_decoder::Error,
+    },
+
+    #[error(transparent)]
+    Gif {
+        #[from]
+        source: gif::DecodingError,
+    },
+
+    #[error(transparent)]
+    WebP {
+        #[from]
+        source: image_webp::DecodingError,
+    },
+
+    #[error(transparent)]
+    Svg {
+        #[from]
+        source: resvg::usvg::Error,
+    },
+
+    #[error("{0} are not supported by Runway.")]
+    Unsupported(&'static str),
+
+    #[error("Image is malformed: {0}.")]
+    Malformed(&'static str),
+
+    #[error("Image format could not be recognized.")]
+    UnknownFormat,
 }
 
 #[cfg(test)]
@@ -198,6 +350,242 @@ mod test {
         target.blit(&source, (4, 4));
     }
 
+    fn encode(
+        color_type: png::ColorType,
+        bit_depth: png::BitDepth,
+        data: &[u8],
+        palette: Option<&[u8]>,
+        trns: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut output = Vec::new();
+
+        let mut encoder = png::Encoder::new(&mut output, 2, 1);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        if let Some(palette) = palette {
+            encoder.set_palette(palette);
+        }
+        if let Some(trns) = trns {
+            encoder.set_trns(trns);
+        }
+
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+
+        output
+    }
+
+    fn decoded_pixels(png: &[u8]) -> Vec<Pixel> {
+        let image = Image::decode_png(png).unwrap();
+        assert_eq!(image.size(), (2, 1));
+        vec![image.get_pixel((0, 0)), image.get_pixel((1, 0))]
+    }
+
+    #[test]
+    fn decode_png_rgb() {
+        use png::{BitDepth, ColorType};
+
+        let png = encode(
+            ColorType::Rgb,
+            BitDepth::Eight,
+            &[1, 2, 3, 4, 5, 6],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(1, 2, 3, 255), Pixel::new(4, 5, 6, 255)]
+        );
+
+        let png = encode(
+            ColorType::Rgb,
+            BitDepth::Sixteen,
+            &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(1, 2, 3, 255), Pixel::new(4, 5, 6, 255)]
+        );
+    }
+
+    #[test]
+    fn decode_png_rgba() {
+        use png::{BitDepth, ColorType};
+
+        let png = encode(
+            ColorType::Rgba,
+            BitDepth::Eight,
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(1, 2, 3, 4), Pixel::new(5, 6, 7, 8)]
+        );
+
+        let png = encode(
+            ColorType::Rgba,
+            BitDepth::Sixteen,
+            &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0, 7, 0, 8, 0],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(1, 2, 3, 4), Pixel::new(5, 6, 7, 8)]
+        );
+    }
+
+    #[test]
+    fn decode_png_grayscale() {
+        use png::{BitDepth, ColorType};
+
+        let png = encode(ColorType::Grayscale, BitDepth::Eight, &[10, 20], None, None);
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(10, 10, 10, 255), Pixel::new(20, 20, 20, 255)]
+        );
+
+        // Two 1-bit pixels packed into one byte
+        let png = encode(
+            ColorType::Grayscale,
+            BitDepth::One,
+            &[0b1000_0000],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(255, 255, 255, 255), Pixel::new(0, 0, 0, 255)]
+        );
+
+        let png = encode(
+            ColorType::Grayscale,
+            BitDepth::Sixteen,
+            &[10, 0, 20, 0],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(10, 10, 10, 255), Pixel::new(20, 20, 20, 255)]
+        );
+
+        // tRNS marks one gray level as transparent
+        let png = encode(
+            ColorType::Grayscale,
+            BitDepth::Eight,
+            &[10, 20],
+            None,
+            Some(&[0, 20]),
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(10, 10, 10, 255), Pixel::new(20, 20, 20, 0)]
+        );
+    }
+
+    #[test]
+    fn decode_png_grayscale_alpha() {
+        use png::{BitDepth, ColorType};
+
+        let png = encode(
+            ColorType::GrayscaleAlpha,
+            BitDepth::Eight,
+            &[10, 1, 20, 2],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(10, 10, 10, 1), Pixel::new(20, 20, 20, 2)]
+        );
+
+        let png = encode(
+            ColorType::GrayscaleAlpha,
+            BitDepth::Sixteen,
+            &[10, 0, 1, 0, 20, 0, 2, 0],
+            None,
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(10, 10, 10, 1), Pixel::new(20, 20, 20, 2)]
+        );
+    }
+
+    #[test]
+    fn decode_png_indexed() {
+        use png::{BitDepth, ColorType};
+
+        let palette = [1, 2, 3, 4, 5, 6];
+
+        let png = encode(
+            ColorType::Indexed,
+            BitDepth::Eight,
+            &[1, 0],
+            Some(&palette),
+            None,
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(4, 5, 6, 255), Pixel::new(1, 2, 3, 255)]
+        );
+
+        // tRNS gives the first palette entry an alpha, and the rest are opaque
+        let png = encode(
+            ColorType::Indexed,
+            BitDepth::Four,
+            &[0b0001_0000],
+            Some(&palette),
+            Some(&[7]),
+        );
+        assert_eq!(
+            decoded_pixels(&png),
+            vec![Pixel::new(4, 5, 6, 255), Pixel::new(1, 2, 3, 7)]
+        );
+    }
+
+    #[test]
+    fn encode_indexed_png() {
+        let mut source = Image::new_empty_rgba8((3, 1));
+        source.set_pixel((0, 0), Pixel::new(255, 0, 0, 255));
+        source.set_pixel((1, 0), Pixel::new(0, 0, 255, 128));
+
+        let mut png = Vec::new();
+        assert!(source.encode_indexed_png(&mut png).unwrap());
+
+        let reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert_eq!(reader.info().trns.as_deref(), Some([0, 128].as_slice()));
+
+        let decoded = Image::decode_png(png.as_slice()).unwrap();
+        assert_eq!(decoded.data(), source.data());
+
+        // Too many colors for a palette
+        let mut source = Image::new_empty_rgba8((257, 1));
+        for x in 0..257 {
+            source.set_pixel((x, 0), Pixel::new(x as u8, (x / 256) as u8, 0, 255));
+        }
+        let mut png = Vec::new();
+        assert!(!source.encode_indexed_png(&mut png).unwrap());
+        assert!(png.is_empty());
+    }
+
+    #[test]
+    fn is_opaque() {
+        let mut image = Image::new_empty_rgba8((2, 1));
+        assert!(!image.is_opaque());
+
+        image.set_pixel((0, 0), Pixel::new(0, 0, 0, 255));
+        image.set_pixel((1, 0), Pixel::new(0, 0, 0, 255));
+        assert!(image.is_opaque());
+    }
+
     #[test]
     fn get_pixel() {
         let source = Image::new_empty_rgba8((4, 4));
@@ -0,0 +1,228 @@
+//! Reads EXIF orientation and removes metadata from PNG and JPEG files without re-encoding them.
+
+use super::image::Image;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Ancillary chunk that's kept because it changes how the image looks
+const PNG_TRANSPARENCY: &[u8] = b"tRNS";
+
+/// Returns the EXIF orientation of a JPEG, from 1 to 8, if it has one.
+pub(super) fn exif_orientation(jpeg: &[u8]) -> Option<u16> {
+    let exif = jpeg_segments(jpeg)?
+        .into_iter()
+        .find(|(marker, data)| *marker == 0xE1 && data.starts_with(b"Exif\0\0"))?
+        .1;
+    let tiff = &exif[6..];
+
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    // The first directory's entries are 12 bytes each, after their count
+    let ifd = read_u32(4)? as usize;
+    let count = read_u16(ifd)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| read_u16(entry) == Some(0x0112))
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Rotates and flips an image so it's upright for an EXIF orientation.
+pub(super) fn apply_orientation(image: &Image, orientation: u16) -> Image {
+    let (width, height) = image.size();
+    let transposed = orientation >= 5;
+
+    let new_size = if transposed {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let mut out = Image::new_empty_rgba8(new_size);
+
+    for y in 0..new_size.1 {
+        for x in 0..new_size.0 {
+            let (max_x, max_y) = (new_size.0 - 1, new_size.1 - 1);
+
+            // The source pixel that ends up at (x, y)
+            let source = match orientation {
+                2 => (max_x - x, y),
+                3 => (max_x - x, max_y - y),
+                4 => (x, max_y - y),
+                5 => (y, x),
+                6 => (y, max_x - x),
+                7 => (max_y - y, max_x - x),
+                8 => (max_y - y, x),
+                _ => (x, y),
+            };
+            out.set_pixel((x, y), image.get_pixel(source));
+        }
+    }
+
+    out
+}
+
+/// Removes every ancillary chunk except transparency, such as color profiles, gamma, and text.
+/// Returns `None` if the file isn't a valid PNG.
+pub(super) fn strip_png(png: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = png.strip_prefix(PNG_SIGNATURE)?;
+    let mut output = PNG_SIGNATURE.to_vec();
+
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+
+        // Length, type, data, and CRC
+        let chunk = rest.get(..12 + len)?;
+        let kind = &chunk[4..8];
+
+        // Critical chunks start with an uppercase letter
+        if kind[0].is_ascii_uppercase() || kind == PNG_TRANSPARENCY {
+            output.extend_from_slice(chunk);
+        }
+
+        rest = &rest[12 + len..];
+    }
+
+    Some(output)
+}
+
+/// Removes application segments other than JFIF and Adobe, which affect decoding, and comments.
+/// Returns `None` if the file isn't a valid JPEG.
+pub(super) fn strip_jpeg(jpeg: &[u8]) -> Option<Vec<u8>> {
+    let segments = jpeg_segments(jpeg)?;
+
+    let mut output = b"\xFF\xD8".to_vec();
+    let mut end = 2;
+    for (marker, data) in segments {
+        end += 4 + data.len();
+
+        let is_metadata = matches!(marker, 0xE1..=0xED | 0xEF | 0xFE);
+        if !is_metadata {
+            output.extend([0xFF, marker]);
+            output.extend(((data.len() + 2) as u16).to_be_bytes());
+            output.extend_from_slice(data);
+        }
+    }
+
+    // Everything from the start of scan is image data
+    output.extend_from_slice(&jpeg[end..]);
+
+    Some(output)
+}
+
+// Splits the segments before the start of scan into their markers and data
+fn jpeg_segments(jpeg: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut rest = jpeg.strip_prefix(b"\xFF\xD8")?;
+    let mut segments = Vec::new();
+
+    loop {
+        let (&[0xFF, marker], after) = rest.split_first_chunk::<2>()? else {
+            return None;
+        };
+
+        // Start of scan
+        if marker == 0xDA {
+            return Some(segments);
+        }
+
+        let len = u16::from_be_bytes(after.get(..2)?.try_into().ok()?) as usize;
+        let data = after.get(2..len)?;
+        segments.push((marker, data));
+
+        rest = &after[len..];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    #[test]
+    fn orientation() {
+        // 2x1 with red on the left
+        let mut image = Image::new_empty_rgba8((2, 1));
+        image.set_pixel((0, 0), Pixel::new(255, 0, 0, 255));
+
+        // Rotated 90 degrees clockwise, so red is on top
+        let rotated = apply_orientation(&image, 6);
+        assert_eq!(rotated.size(), (1, 2));
+        assert_eq!(rotated.get_pixel((0, 0)).r, 255);
+
+        let rotated = apply_orientation(&image, 8);
+        assert_eq!(rotated.get_pixel((0, 1)).r, 255);
+
+        let flipped = apply_orientation(&image, 2);
+        assert_eq!(flipped.get_pixel((1, 0)).r, 255);
+    }
+
+    #[test]
+    fn exif() {
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        exif.extend([0, 1]);
+        exif.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        exif.extend([0; 4]);
+
+        let mut jpeg = b"\xFF\xD8\xFF\xE1".to_vec();
+        jpeg.extend(((exif.len() + 2) as u16).to_be_bytes());
+        jpeg.extend(&exif);
+        jpeg.extend(b"\xFF\xFE\0\x05abc");
+        jpeg.extend(b"\xFF\xDB\0\x03x");
+        jpeg.extend(b"\xFF\xDA\0\x02data\xFF\xD9");
+
+        assert_eq!(exif_orientation(&jpeg), Some(6));
+
+        let stripped = strip_jpeg(&jpeg).unwrap();
+        assert_eq!(
+            stripped,
+            b"\xFF\xD8\xFF\xDB\0\x03x\xFF\xDA\0\x02data\xFF\xD9"
+        );
+        assert_eq!(exif_orientation(&stripped), None);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+            chunk.extend(kind);
+            chunk.extend(data);
+            chunk.extend([0; 4]);
+            chunk
+        };
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"tEXt", b"Software\0C:/Users/artist"));
+        png.extend(chunk(b"tRNS", &[0, 0]));
+        png.extend(chunk(b"IDAT", &[1, 2, 3]));
+        png.extend(chunk(b"IEND", &[]));
+
+        let mut expected = PNG_SIGNATURE.to_vec();
+        expected.extend(chunk(b"IHDR", &[0; 13]));
+        expected.extend(chunk(b"tRNS", &[0, 0]));
+        expected.extend(chunk(b"IDAT", &[1, 2, 3]));
+        expected.extend(chunk(b"IEND", &[]));
+
+        assert_eq!(strip_png(&png), Some(expected));
+        assert_eq!(strip_png(b"not a png"), None);
+    }
+}
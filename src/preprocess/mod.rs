@@ -1,40 +1,508 @@
 use rbxcloud::rbx::assets::AssetType;
 use thiserror::Error;
 
-use crate::asset::Asset;
+use crate::{
+    asset::Asset,
+    asset_ident::AssetKind,
+    state::{AssetMetadata, Sprite},
+};
 
 mod alpha_bleed;
+mod audio;
+mod convert;
 mod image;
+mod metadata;
+mod optimize;
+mod pack;
+mod quantize;
+mod resize;
+mod svg;
+mod trim;
+
+pub use self::{optimize::PngOptimization, resize::ResizeFilter};
 
 use self::{
     alpha_bleed::alpha_bleed,
+    audio::{mp3_duration, ogg_duration},
+    convert::decode_any,
     image::{DecodeError, Image},
+    metadata::{apply_orientation, exif_orientation, strip_jpeg, strip_png},
+    optimize::optimize_png,
+    pack::pack,
+    quantize::quantize,
+    resize::{fit_within, resize},
+    svg::rasterize,
+    trim::trim,
 };
 
+// Quality used when re-encoding resized JPEGs from inputs without `jpeg_quality`
+const JPEG_QUALITY: u8 = 90;
+
+// Transparent pixels kept around images from inputs with `trim` and no `trim_padding`
+const TRIM_PADDING: u32 = 1;
+
+// The size of an image before it was shrunk to fit the input's `max_size`
+struct Resized {
+    original_size: (u32, u32),
+    scale: f64,
+}
+
+// The area an image was cropped to by the input's `trim`
+struct Cropped {
+    original_size: (u32, u32),
+    offset: (u32, u32),
+}
+
 pub fn preprocess(asset: &mut Asset) -> Result<(), PreprocessError> {
-    if matches!(asset.ident.asset_type(), AssetType::DecalPng) {
-        match Image::decode_png(asset.contents.as_slice()) {
+    // Images that couldn't be packed were already processed before packing,
+    // and assets that failed to process shouldn't be tried again
+    if asset.preprocessed {
+        return Ok(());
+    }
+    asset.preprocessed = true;
+
+    // Generated variants are only shrunk once they're needed
+    if let Some(scale) = asset.dpi_rescale.take() {
+        asset.contents = rescale(asset, scale)?.into();
+    }
+
+    let mut resized = None;
+    let mut cropped = None;
+
+    match asset.ident.asset_type() {
+        AssetType::DecalPng => match decode(asset) {
             Ok(mut image) => {
-                log::debug!("Preprocessing {}: applying alpha bleed", asset.ident);
-                alpha_bleed(&mut image);
+                if asset.input.trim {
+                    let padding = asset.input.trim_padding.unwrap_or(TRIM_PADDING);
+                    if let Some(trimmed) = trim(&image, padding) {
+                        log::debug!(
+                            "Preprocessing {}: trimming from {}x{} to {}x{}",
+                            asset.ident,
+                            image.size().0,
+                            image.size().1,
+                            trimmed.image.size().0,
+                            trimmed.image.size().1
+                        );
 
-                let mut new_contents = Vec::new();
-                image.encode_png(&mut new_contents)?;
+                        cropped = Some(Cropped {
+                            original_size: image.size(),
+                            offset: trimmed.offset,
+                        });
+                        image = trimmed.image;
+                    }
+                }
 
-                asset.contents = debug_ignore::DebugIgnore(new_contents);
-            }
-            Err(DecodeError::ColorType(png::ColorType::Rgb | png::ColorType::Grayscale)) => {
-                // doesn't have transparency
+                resized = shrink(asset, &mut image);
+
+                if asset.ident.is_converted() {
+                    log::debug!("Preprocessing {}: converting to PNG", asset.ident);
+                }
+
+                // Quantizing makes every transparent pixel the same color anyway
+                let opaque = image.is_opaque();
+                if !opaque && asset.input.quantize.is_none() {
+                    log::debug!("Preprocessing {}: applying alpha bleed", asset.ident);
+                    alpha_bleed(&mut image);
+                }
+
+                if let Some(options) = &asset.input.quantize {
+                    log::debug!(
+                        "Preprocessing {}: quantizing to {} colors",
+                        asset.ident,
+                        options.colors
+                    );
+                    image = quantize(&image, options.colors, options.dither);
+                }
+
+                // Keep the original file if there's nothing to do
+                if !opaque
+                    || resized.is_some()
+                    || cropped.is_some()
+                    || asset.input.quantize.is_some()
+                    || asset.ident.is_converted()
+                {
+                    let mut new_contents = Vec::new();
+
+                    // Quantized images fit in a palette, which is much smaller
+                    let indexed = asset.input.quantize.is_some()
+                        && image.encode_indexed_png(&mut new_contents)?;
+                    if !indexed {
+                        image.encode_png(&mut new_contents)?;
+                    }
+
+                    asset.contents = debug_ignore::DebugIgnore(new_contents);
+                }
             }
+            // Roblox won't accept the original file, so there's nothing to fall back to
+            Err(e) if asset.ident.is_converted() => return Err(e.into()),
             Err(e) => {
-                log::warn!("Preprocessing {}: skipping alpha bleed: {}", asset.ident, e);
+                log::warn!(
+                    "Preprocessing {}: skipping image processing: {}",
+                    asset.ident,
+                    e
+                );
+            }
+        },
+        AssetType::DecalJpeg
+            if asset.input.max_size.is_some()
+                || asset.input.jpeg_quality.is_some()
+                || asset.input.strip_metadata =>
+        {
+            match decode(asset) {
+                Ok(mut image) => {
+                    resized = shrink(asset, &mut image);
+
+                    if resized.is_some() || asset.input.jpeg_quality.is_some() || is_rotated(asset)
+                    {
+                        let mut new_contents = Vec::new();
+                        image.encode_jpeg(&mut new_contents, jpeg_quality(asset))?;
+
+                        asset.contents = debug_ignore::DebugIgnore(new_contents);
+                    } else if asset.input.strip_metadata {
+                        // Re-encoding drops metadata, but otherwise it can be removed losslessly
+                        if let Some(stripped) = strip_jpeg(asset.contents.as_slice()) {
+                            asset.contents = debug_ignore::DebugIgnore(stripped);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Preprocessing {}: skipping image processing: {}",
+                        asset.ident,
+                        e
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if matches!(asset.ident.asset_type(), AssetType::DecalPng) {
+        if asset.input.strip_metadata {
+            if let Some(stripped) = strip_png(asset.contents.as_slice()) {
+                asset.contents = debug_ignore::DebugIgnore(stripped);
             }
         }
+
+        if let Some(level) = asset.input.optimize {
+            optimize(asset, level);
+        }
     }
 
+    asset.metadata = Some(measure(asset, resized, cropped));
+
     Ok(())
 }
 
+fn jpeg_quality(asset: &Asset) -> u8 {
+    asset.input.jpeg_quality.unwrap_or(JPEG_QUALITY)
+}
+
+// Replaces a PNG asset's contents with an optimized version if it's smaller
+fn optimize(asset: &mut Asset, level: PngOptimization) {
+    match optimize_png(asset.contents.as_slice(), level) {
+        Ok(optimized) if optimized.len() < asset.contents.len() => {
+            asset.bytes_saved = (asset.contents.len() - optimized.len()) as u64;
+            log::debug!(
+                "Preprocessing {}: optimized PNG, saving {} bytes",
+                asset.ident,
+                asset.bytes_saved
+            );
+
+            asset.contents = debug_ignore::DebugIgnore(optimized);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::warn!(
+                "Preprocessing {}: skipping optimization: {}",
+                asset.ident,
+                e
+            );
+        }
+    }
+}
+
+// Decodes an image asset, whether it's still in its original format or already processed
+fn decode(asset: &Asset) -> Result<Image, DecodeError> {
+    let contents = asset.contents.as_slice();
+    let extension = asset.ident.extension().unwrap_or_default();
+
+    if extension.eq_ignore_ascii_case("svg") && !contents.starts_with(b"\x89PNG") {
+        return rasterize(contents, &asset.input);
+    }
+
+    let mut image = decode_any(contents, &extension)?;
+
+    // Roblox ignores EXIF orientation, so rotate the pixels if metadata is being removed
+    if asset.input.strip_metadata {
+        if let Some(orientation) = exif_orientation(contents) {
+            image = apply_orientation(&image, orientation);
+        }
+    }
+
+    Ok(image)
+}
+
+// Whether decoding the asset rotates or flips it, so it needs to be re-encoded
+fn is_rotated(asset: &Asset) -> bool {
+    asset.input.strip_metadata
+        && exif_orientation(asset.contents.as_slice()).is_some_and(|o| o != 1)
+}
+
+// Shrinks an image to fit within the input's `max_size`, if it's set and the image is larger
+fn shrink(asset: &Asset, image: &mut Image) -> Option<Resized> {
+    let original_size = image.size();
+    let new_size = fit_within(original_size, asset.input.max_size?)?;
+
+    log::debug!(
+        "Preprocessing {}: resizing from {}x{} to {}x{}",
+        asset.ident,
+        original_size.0,
+        original_size.1,
+        new_size.0,
+        new_size.1
+    );
+    *image = resize(image, new_size, asset.input.resize_filter);
+
+    Some(Resized {
+        original_size,
+        scale: new_size.0 as f64 / original_size.0 as f64,
+    })
+}
+
+// Resizes the original contents of an image asset by `scale`, keeping its format
+fn rescale(asset: &Asset, scale: f64) -> Result<Vec<u8>, PreprocessError> {
+    let is_jpeg = matches!(asset.ident.asset_type(), AssetType::DecalJpeg);
+    let image = decode(asset)?;
+
+    let image = resize(
+        &image,
+        scaled(image.size(), scale),
+        asset.input.resize_filter,
+    );
+
+    let mut output = Vec::new();
+    if is_jpeg {
+        image.encode_jpeg(&mut output, jpeg_quality(asset))?;
+    } else {
+        image.encode_png(&mut output)?;
+    }
+
+    Ok(output)
+}
+
+fn scaled(size: (u32, u32), scale: f64) -> (u32, u32) {
+    (
+        ((size.0 as f64 * scale).round() as u32).max(1),
+        ((size.1 as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// A spritesheet encoded as a PNG with the index and area of each image packed into it.
+pub struct Spritesheet {
+    pub contents: Vec<u8>,
+    pub sprites: Vec<(usize, Sprite)>,
+}
+
+/// Packs processed image assets into spritesheets.
+/// Also returns the indices of assets that can't be packed, which should be synced on their own.
+pub fn pack_assets(assets: &[&Asset]) -> (Vec<Spritesheet>, Vec<usize>) {
+    let mut images = Vec::new();
+    let mut indices = Vec::new();
+    let mut unpacked = Vec::new();
+
+    for (i, asset) in assets.iter().enumerate() {
+        let image = match asset.ident.asset_type() {
+            AssetType::DecalPng | AssetType::DecalJpeg => decode(asset),
+            _ => {
+                unpacked.push(i);
+                continue;
+            }
+        };
+
+        match image {
+            Ok(image) => {
+                images.push(image);
+                indices.push(i);
+            }
+            Err(e) => {
+                log::warn!("Packing {}: skipping: {}", asset.ident, e);
+                unpacked.push(i);
+            }
+        }
+    }
+
+    let (atlases, too_large) = pack(&images);
+
+    for i in too_large {
+        log::warn!(
+            "Packing {}: too large for a spritesheet, syncing on its own",
+            assets[indices[i]].ident
+        );
+        unpacked.push(indices[i]);
+    }
+
+    let mut sheets = Vec::new();
+    for atlas in atlases {
+        let sprites = atlas
+            .sprites
+            .into_iter()
+            .map(|(i, sprite)| (indices[i], sprite))
+            .collect::<Vec<_>>();
+
+        let mut contents = Vec::new();
+        match atlas.image.encode_png(&mut contents) {
+            Ok(()) => sheets.push(Spritesheet { contents, sprites }),
+            Err(e) => {
+                log::warn!(
+                    "Packing: failed to encode spritesheet, syncing its images on their own: {}",
+                    e
+                );
+                unpacked.extend(sprites.into_iter().map(|(i, _)| i));
+            }
+        }
+    }
+
+    unpacked.sort();
+
+    (sheets, unpacked)
+}
+
+/// Returns a PNG of a processed image asset shrunk to fit within `max_size`,
+/// or `None` if the asset isn't an image that can be decoded.
+pub fn thumbnail(asset: &Asset, max_size: u32) -> Result<Option<Vec<u8>>, PreprocessError> {
+    let image = match asset.ident.asset_type() {
+        AssetType::DecalPng | AssetType::DecalJpeg => decode(asset)?,
+        _ => return Ok(None),
+    };
+
+    let image = match fit_within(image.size(), max_size) {
+        Some(new_size) => resize(&image, new_size, asset.input.resize_filter),
+        None => image,
+    };
+
+    let mut output = Vec::new();
+    image.encode_png(&mut output)?;
+
+    Ok(Some(output))
+}
+
+/// Measures an asset for codegen without processing it, predicting the size its images
+/// would be processed to. Images are only decoded when trimming, rotation or rasterizing
+/// could change their size, and the size in bytes is the source file's.
+pub fn measure_unprocessed(asset: &Asset) -> Result<AssetMetadata, PreprocessError> {
+    if asset.ident.kind() != AssetKind::Image {
+        return Ok(metadata(asset, None, None, None));
+    }
+
+    let is_svg = asset
+        .ident
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+    let trims = asset.input.trim && matches!(asset.ident.asset_type(), AssetType::DecalPng);
+
+    let header_size = imagesize::blob_size(asset.contents.as_slice())
+        .ok()
+        .filter(|_| !is_svg && !trims && !is_rotated(asset));
+
+    let mut cropped = None;
+    let mut size = match header_size {
+        Some(size) => (size.width as u32, size.height as u32),
+        None => {
+            let mut image = decode(asset)?;
+            if let Some(scale) = asset.dpi_rescale {
+                image = resize(
+                    &image,
+                    scaled(image.size(), scale),
+                    asset.input.resize_filter,
+                );
+            }
+
+            if trims {
+                let padding = asset.input.trim_padding.unwrap_or(TRIM_PADDING);
+                if let Some(trimmed) = trim(&image, padding) {
+                    cropped = Some(Cropped {
+                        original_size: image.size(),
+                        offset: trimmed.offset,
+                    });
+                    image = trimmed.image;
+                }
+            }
+
+            image.size()
+        }
+    };
+
+    if let (Some(scale), Some(_)) = (asset.dpi_rescale, header_size) {
+        size = scaled(size, scale);
+    }
+
+    let mut resized = None;
+    if let Some(new_size) = asset.input.max_size.and_then(|max| fit_within(size, max)) {
+        resized = Some(Resized {
+            original_size: size,
+            scale: new_size.0 as f64 / size.0 as f64,
+        });
+        size = new_size;
+    }
+
+    Ok(metadata(asset, Some(size), resized, cropped))
+}
+
+// Measure the processed contents for codegen
+fn measure(asset: &Asset, resized: Option<Resized>, cropped: Option<Cropped>) -> AssetMetadata {
+    let size = match asset.ident.kind() {
+        AssetKind::Image => match imagesize::blob_size(asset.contents.as_slice()) {
+            Ok(size) => Some((size.width as u32, size.height as u32)),
+            Err(e) => {
+                log::warn!("Preprocessing {}: failed to read size: {}", asset.ident, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    metadata(asset, size, resized, cropped)
+}
+
+fn metadata(
+    asset: &Asset,
+    size: Option<(u32, u32)>,
+    resized: Option<Resized>,
+    cropped: Option<Cropped>,
+) -> AssetMetadata {
+    let contents = asset.contents.as_slice();
+
+    let duration = match asset.ident.asset_type() {
+        AssetType::AudioOgg => ogg_duration(contents),
+        AssetType::AudioMp3 => mp3_duration(contents),
+        _ => None,
+    };
+
+    if asset.ident.kind() == AssetKind::Audio && duration.is_none() {
+        log::warn!("Preprocessing {}: failed to read duration", asset.ident);
+    }
+
+    // Trimming happens first, so it has the size of the source file
+    let original_size = cropped
+        .as_ref()
+        .map(|c| c.original_size)
+        .or(resized.as_ref().map(|r| r.original_size));
+
+    AssetMetadata {
+        hash: asset.hash.clone(),
+        size: contents.len() as u64,
+        width: size.map(|s| s.0),
+        height: size.map(|s| s.1),
+        duration,
+        original_width: original_size.map(|s| s.0),
+        original_height: original_size.map(|s| s.1),
+        scale: resized.map(|r| r.scale),
+        trim_x: cropped.as_ref().map(|c| c.offset.0),
+        trim_y: cropped.as_ref().map(|c| c.offset.1),
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum PreprocessError {
     #[error(transparent)]
@@ -42,4 +510,90 @@ pub enum PreprocessError {
 
     #[error(transparent)]
     EncodePng(#[from] png::EncodingError),
+
+    #[error(transparent)]
+    EncodeJpeg(#[from] jpeg_encoder::EncodingError),
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{asset_ident::AssetIdent, config::InputConfig};
+
+    #[test]
+    fn unpacked_sprite_processed_once() {
+        let mut image = Image::new_empty_rgba8((2048, 16));
+        image.set_pixel((0, 0), image::Pixel::new(255, 0, 0, 255));
+        let mut contents = Vec::new();
+        image.encode_png(&mut contents).unwrap();
+
+        let input = InputConfig {
+            max_size: Some(1500),
+            pack: true,
+            ..Default::default()
+        };
+        let ident = AssetIdent::from_paths(Path::new(""), Path::new("wide.png")).unwrap();
+        let mut asset = Asset::new(ident, contents, String::new(), &input);
+
+        preprocess(&mut asset).unwrap();
+        let processed = asset.contents.clone();
+
+        // Still too large for a spritesheet after resizing
+        let (sheets, unpacked) = pack_assets(&[&asset]);
+        assert!(sheets.is_empty());
+        assert_eq!(unpacked, vec![0]);
+
+        // Syncing it on its own doesn't process it again
+        preprocess(&mut asset).unwrap();
+        assert_eq!(*asset.contents, *processed);
+
+        let metadata = asset.metadata.unwrap();
+        assert_eq!((metadata.width, metadata.height), (Some(1500), Some(12)));
+        assert_eq!(metadata.original_width, Some(2048));
+    }
+
+    #[test]
+    fn measure_matches_processed() {
+        let mut image = Image::new_empty_rgba8((300, 200));
+        for y in 50..150 {
+            for x in 20..220 {
+                image.set_pixel((x, y), image::Pixel::new(0, 0, 255, 255));
+            }
+        }
+        let mut contents = Vec::new();
+        image.encode_png(&mut contents).unwrap();
+
+        let ident = AssetIdent::from_paths(Path::new(""), Path::new("box.png")).unwrap();
+        for input in [
+            InputConfig {
+                max_size: Some(100),
+                ..Default::default()
+            },
+            InputConfig {
+                max_size: Some(100),
+                trim: true,
+                ..Default::default()
+            },
+        ] {
+            let mut asset = Asset::new(ident.clone(), contents.clone(), String::new(), &input);
+            asset.dpi_rescale = Some(0.5);
+
+            let measured = measure_unprocessed(&asset).unwrap();
+            preprocess(&mut asset).unwrap();
+            let processed = asset.metadata.unwrap();
+
+            assert_eq!(measured.width, processed.width);
+            assert_eq!(measured.height, processed.height);
+            assert_eq!(measured.original_width, processed.original_width);
+            assert_eq!(measured.original_height, processed.original_height);
+            assert_eq!(measured.scale, processed.scale);
+            assert_eq!(measured.trim_x, processed.trim_x);
+            assert_eq!(measured.trim_y, processed.trim_y);
+        }
+    }
 }
@@ -0,0 +1,73 @@
+use std::num::NonZeroU8;
+
+use serde::Deserialize;
+
+/// How hard to try to shrink PNGs without changing their pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngOptimization {
+    /// Tries a few filters with a fast compressor.
+    Fast,
+
+    /// Tries every filter and compresses with Zopfli, which is much slower.
+    Max,
+}
+
+/// Losslessly recompresses a PNG, reducing its color type and bit depth where possible
+/// and removing chunks that don't affect how it's displayed.
+pub(super) fn optimize_png(
+    contents: &[u8],
+    level: PngOptimization,
+) -> Result<Vec<u8>, oxipng::PngError> {
+    let mut options = match level {
+        PngOptimization::Fast => oxipng::Options::from_preset(2),
+        PngOptimization::Max => {
+            let mut options = oxipng::Options::max_compression();
+            options.deflate = oxipng::Deflaters::Zopfli {
+                iterations: NonZeroU8::new(15).unwrap(),
+            };
+            options
+        }
+    };
+
+    options.strip = oxipng::StripChunks::Safe;
+
+    // The colors of transparent pixels are set by alpha bleeding, so they need to be kept
+    options.optimize_alpha = false;
+
+    oxipng::optimize_from_memory(contents, &options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::{Image, Pixel};
+
+    #[test]
+    fn lossless() {
+        let mut image = Image::new_empty_rgba8((32, 32));
+        for y in 0..32 {
+            for x in 0..32 {
+                let pixel = if (x / 8 + y / 8) % 2 == 0 {
+                    Pixel::new(255, 0, 0, 255)
+                } else {
+                    Pixel::new(0, 0, 255, 0)
+                };
+                image.set_pixel((x, y), pixel);
+            }
+        }
+
+        let mut contents = Vec::new();
+        image.encode_png(&mut contents).unwrap();
+
+        for level in [PngOptimization::Fast, PngOptimization::Max] {
+            let optimized = optimize_png(&contents, level).unwrap();
+            assert!(optimized.len() < contents.len());
+
+            // Fully transparent pixels keep their color
+            let decoded = Image::decode_png(optimized.as_slice()).unwrap();
+            assert_eq!(decoded.get_pixel((0, 0)), Pixel::new(255, 0, 0, 255));
+            assert_eq!(decoded.get_pixel((8, 0)), Pixel::new(0, 0, 255, 0));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Packs images into spritesheets using shelves sorted by height.
+
+use crate::state::Sprite;
+
+use super::{alpha_bleed::alpha_bleed, image::Image};
+
+/// The largest width and height of a spritesheet.
+const ATLAS_SIZE: u32 = 1024;
+
+// Space left between sprites so neighbors don't bleed into each other when filtered
+const PADDING: u32 = 2;
+
+/// A spritesheet and the area each packed image was placed in.
+pub(super) struct Atlas {
+    pub image: Image,
+
+    /// The index of each packed image in the input with its area in the spritesheet.
+    pub sprites: Vec<(usize, Sprite)>,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+#[derive(Default)]
+struct Bin {
+    shelves: Vec<Shelf>,
+    next_y: u32,
+}
+
+impl Bin {
+    // Finds a spot for a rectangle, starting a new shelf if none of the current ones fit
+    fn insert(&mut self, (width, height): (u32, u32)) -> Option<(u32, u32)> {
+        for shelf in &mut self.shelves {
+            if height <= shelf.height && shelf.next_x + width <= ATLAS_SIZE {
+                let pos = (shelf.next_x, shelf.y);
+                shelf.next_x += width + PADDING;
+                return Some(pos);
+            }
+        }
+
+        if self.next_y + height > ATLAS_SIZE {
+            return None;
+        }
+
+        let y = self.next_y;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            next_x: width + PADDING,
+        });
+        self.next_y += height + PADDING;
+
+        Some((0, y))
+    }
+}
+
+/// Packs images into as few spritesheets as possible.
+/// Returns the spritesheets and the indices of images too large to fit in one.
+/// The result only depends on the order and contents of `images`.
+pub(super) fn pack(images: &[Image]) -> (Vec<Atlas>, Vec<usize>) {
+    let mut order = (0..images.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| {
+        let (width, height) = images[i].size();
+        (std::cmp::Reverse(height), std::cmp::Reverse(width), i)
+    });
+
+    let mut bins: Vec<(Bin, Vec<(usize, Sprite)>)> = Vec::new();
+    let mut unpacked = Vec::new();
+
+    for i in order {
+        let size = images[i].size();
+        if size.0 > ATLAS_SIZE || size.1 > ATLAS_SIZE {
+            unpacked.push(i);
+            continue;
+        }
+
+        let placed = bins
+            .iter_mut()
+            .find_map(|(bin, sprites)| bin.insert(size).map(|pos| (pos, sprites)));
+
+        let sprite = |(x, y): (u32, u32)| Sprite {
+            x,
+            y,
+            width: size.0,
+            height: size.1,
+        };
+
+        match placed {
+            Some((pos, sprites)) => sprites.push((i, sprite(pos))),
+            None => {
+                let mut bin = Bin::default();
+                let pos = bin.insert(size).expect("image should fit in an empty bin");
+                bins.push((bin, vec![(i, sprite(pos))]));
+            }
+        }
+    }
+
+    let atlases = bins
+        .into_iter()
+        .map(|(_, mut sprites)| {
+            sprites.sort_by_key(|(i, _)| *i);
+
+            // Crop the spritesheet to the area that was used
+            let width = sprites.iter().map(|(_, s)| s.x + s.width).max().unwrap();
+            let height = sprites.iter().map(|(_, s)| s.y + s.height).max().unwrap();
+
+            let mut image = Image::new_empty_rgba8((width, height));
+            for (i, sprite) in &sprites {
+                image.blit(&images[*i], (sprite.x, sprite.y));
+            }
+
+            // Fill the gaps between sprites with their edge colors too
+            alpha_bleed(&mut image);
+
+            Atlas { image, sprites }
+        })
+        .collect();
+
+    (atlases, unpacked)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    fn solid(size: (u32, u32), pixel: Pixel) -> Image {
+        let mut image = Image::new_empty_rgba8(size);
+        for y in 0..size.1 {
+            for x in 0..size.0 {
+                image.set_pixel((x, y), pixel);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn shelves() {
+        let red = Pixel::new(255, 0, 0, 255);
+        let blue = Pixel::new(0, 0, 255, 255);
+        let images = vec![
+            solid((16, 16), red),
+            solid((32, 32), blue),
+            solid((16, 8), red),
+        ];
+
+        let (atlases, unpacked) = pack(&images);
+        assert!(unpacked.is_empty());
+        assert_eq!(atlases.len(), 1);
+
+        // The tallest image starts the first shelf, and the rest follow it
+        let atlas = &atlases[0];
+        let sprites = atlas.sprites.iter().map(|(_, s)| *s).collect::<Vec<_>>();
+        assert_eq!(
+            sprites,
+            vec![
+                Sprite {
+                    x: 34,
+                    y: 0,
+                    width: 16,
+                    height: 16
+                },
+                Sprite {
+                    x: 0,
+                    y: 0,
+                    width: 32,
+                    height: 32
+                },
+                Sprite {
+                    x: 52,
+                    y: 0,
+                    width: 16,
+                    height: 8
+                },
+            ]
+        );
+
+        assert_eq!(atlas.image.size(), (68, 32));
+        assert_eq!(atlas.image.get_pixel((40, 8)), red);
+        assert_eq!(atlas.image.get_pixel((8, 8)), blue);
+
+        // Padding is transparent, but takes the colors of the nearest sprites
+        let (left, right) = (
+            atlas.image.get_pixel((32, 8)),
+            atlas.image.get_pixel((33, 8)),
+        );
+        assert_eq!((left.a, right.a), (0, 0));
+        assert!(left.b > left.r && right.r > right.b);
+    }
+
+    #[test]
+    fn overflow() {
+        let pixel = Pixel::new(0, 0, 0, 255);
+        let images = vec![
+            solid((600, 600), pixel),
+            solid((600, 600), pixel),
+            solid((2048, 16), pixel),
+        ];
+
+        let (atlases, unpacked) = pack(&images);
+        assert_eq!(unpacked, vec![2]);
+        assert_eq!(atlases.len(), 2);
+        assert_eq!(atlases[0].sprites[0].0, 0);
+        assert_eq!(atlases[1].sprites[0].0, 1);
+    }
+}
@@ -0,0 +1,231 @@
+//! Reduces images to a limited palette using median cut, with optional Floyd-Steinberg dithering.
+
+use std::collections::HashMap;
+
+use super::image::{Image, Pixel};
+
+type Color = [u8; 4];
+
+/// Reduces an image to at most `colors` colors, so it always fits in a palette.
+/// Fully transparent pixels all become transparent black, which counts as one of the colors.
+pub(super) fn quantize(image: &Image, colors: u32, dither: bool) -> Image {
+    let mut histogram: HashMap<Color, u32> = HashMap::new();
+    let mut transparent = false;
+    for p in image.data().chunks_exact(4) {
+        if p[3] > 0 {
+            *histogram.entry([p[0], p[1], p[2], p[3]]).or_default() += 1;
+        } else {
+            transparent = true;
+        }
+    }
+
+    let visible_colors = colors as usize - transparent as usize;
+
+    if histogram.len() <= visible_colors {
+        let mut out = image.clone();
+        let (width, height) = image.size();
+        for y in 0..height {
+            for x in 0..width {
+                if out.get_pixel((x, y)).a == 0 {
+                    out.set_pixel((x, y), Pixel::new(0, 0, 0, 0));
+                }
+            }
+        }
+        return out;
+    }
+
+    let palette = median_cut(histogram.into_iter().collect(), visible_colors);
+
+    let (width, height) = image.size();
+    let mut out = Image::new_empty_rgba8((width, height));
+
+    // Errors carried to the current and next rows, with a column of padding on each side
+    let mut errors = vec![[0.0_f32; 4]; width as usize + 2];
+    let mut next_errors = errors.clone();
+
+    let mut cache: HashMap<Color, usize> = HashMap::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = image.get_pixel((x, y));
+
+            // Left as transparent black
+            if p.a == 0 {
+                continue;
+            }
+
+            let color = [p.r, p.g, p.b, p.a];
+            let index = if dither {
+                let error = errors[x as usize + 1];
+                let mut value = [0.0; 4];
+                for c in 0..4 {
+                    value[c] = (color[c] as f32 + error[c]).clamp(0.0, 255.0);
+                }
+
+                let index = nearest(&palette, value, 4);
+
+                // Spread the difference to the pixels that haven't been visited yet
+                let i = x as usize + 1;
+                for c in 0..4 {
+                    let e = value[c] - palette[index][c] as f32;
+                    errors[i + 1][c] += e * 7.0 / 16.0;
+                    next_errors[i - 1][c] += e * 3.0 / 16.0;
+                    next_errors[i][c] += e * 5.0 / 16.0;
+                    next_errors[i + 1][c] += e * 1.0 / 16.0;
+                }
+
+                index
+            } else {
+                *cache
+                    .entry(color)
+                    .or_insert_with(|| nearest(&palette, color.map(f32::from), 4))
+            };
+
+            let [r, g, b, a] = palette[index];
+            out.set_pixel((x, y), Pixel::new(r, g, b, a));
+        }
+
+        errors = std::mem::replace(&mut next_errors, vec![[0.0; 4]; width as usize + 2]);
+    }
+
+    out
+}
+
+// Splits the colors into boxes until there are `colors` boxes, and averages each one
+fn median_cut(colors: Vec<(Color, u32)>, max_colors: usize) -> Vec<Color> {
+    let mut boxes = vec![colors];
+
+    while boxes.len() < max_colors {
+        // Split the box with the widest range in any channel
+        let Some((index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = (0..4)
+                    .map(|c| {
+                        let min = b.iter().map(|(color, _)| color[c]).min().unwrap();
+                        let max = b.iter().map(|(color, _)| color[c]).max().unwrap();
+                        (c, max - min)
+                    })
+                    .max_by_key(|&(_, range)| range)
+                    .unwrap();
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range)
+        else {
+            break;
+        };
+
+        let mut split = boxes.swap_remove(index);
+        split.sort_unstable_by_key(|(color, _)| (color[channel], *color));
+
+        // Split where half of the pixels are on each side
+        let total = split.iter().map(|(_, count)| *count as u64).sum::<u64>();
+        let mut seen = 0;
+        let mut median = split.len() - 1;
+        for (i, (_, count)) in split.iter().enumerate() {
+            seen += *count as u64;
+            if seen * 2 >= total {
+                median = i + 1;
+                break;
+            }
+        }
+        let median = median.clamp(1, split.len() - 1);
+
+        let rest = split.split_off(median);
+        boxes.push(split);
+        boxes.push(rest);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let total = b.iter().map(|(_, count)| *count as u64).sum::<u64>();
+            let mut sum = [0_u64; 4];
+            for (color, count) in b {
+                for c in 0..4 {
+                    sum[c] += color[c] as u64 * *count as u64;
+                }
+            }
+            sum.map(|s| ((s + total / 2) / total) as u8)
+        })
+        .collect()
+}
+
+// Finds the closest palette entry, comparing only the first `channels` channels
+fn nearest(palette: &[Color], value: [f32; 4], channels: usize) -> usize {
+    palette
+        .iter()
+        .map(|entry| {
+            (0..channels)
+                .map(|c| (entry[c] as f32 - value[c]).powi(2))
+                .sum::<f32>()
+        })
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+        .0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient() -> Image {
+        let mut image = Image::new_empty_rgba8((64, 64));
+        for y in 0..64 {
+            for x in 0..64 {
+                let alpha = if x < 4 { 0 } else { 255 };
+                image.set_pixel((x, y), Pixel::new(x as u8 * 4, y as u8 * 4, 128, alpha));
+            }
+        }
+        image
+    }
+
+    fn count_colors(image: &Image) -> usize {
+        let mut colors = image.data().chunks_exact(4).collect::<Vec<_>>();
+        colors.sort();
+        colors.dedup();
+        colors.len()
+    }
+
+    #[test]
+    fn reduces_colors() {
+        let image = gradient();
+
+        for dither in [false, true] {
+            let quantized = quantize(&image, 16, dither);
+            assert_eq!(count_colors(&quantized), 16);
+
+            // Transparent pixels share one color, and visible pixels stay visible
+            assert_eq!(quantized.get_pixel((0, 0)), Pixel::new(0, 0, 0, 0));
+            assert_eq!(quantized.get_pixel((3, 63)), Pixel::new(0, 0, 0, 0));
+            assert_eq!(quantized.get_pixel((32, 32)).a, 255);
+
+            // Colors stay close to the original
+            let original = image.get_pixel((32, 32));
+            let p = quantized.get_pixel((32, 32));
+            assert!(original.r.abs_diff(p.r) < 32 && original.g.abs_diff(p.g) < 32);
+        }
+    }
+
+    #[test]
+    fn few_colors() {
+        let mut image = Image::new_empty_rgba8((4, 4));
+        image.set_pixel((1, 1), Pixel::new(255, 0, 0, 255));
+
+        let quantized = quantize(&image, 2, true);
+        assert_eq!(quantized.data(), image.data());
+    }
+
+    #[test]
+    fn fits_palette() {
+        // Thousands of colors, with differently colored transparent pixels like after alpha bleed
+        let image = gradient();
+
+        let quantized = quantize(&image, 256, true);
+        assert!(count_colors(&quantized) <= 256);
+        assert!(quantized.encode_indexed_png(&mut Vec::new()).unwrap());
+    }
+}
@@ -0,0 +1,239 @@
+//! Resamples images with a separable filter.
+
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
+use super::image::Image;
+
+/// The filter used to resample images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    /// Sharp, but can ring around hard edges.
+    #[default]
+    Lanczos3,
+
+    /// Slightly softer, without ringing.
+    Mitchell,
+}
+
+impl ResizeFilter {
+    fn support(self) -> f64 {
+        match self {
+            ResizeFilter::Lanczos3 => 3.0,
+            ResizeFilter::Mitchell => 2.0,
+        }
+    }
+
+    fn kernel(self, x: f64) -> f64 {
+        let x = x.abs();
+
+        match self {
+            ResizeFilter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Mitchell => {
+                const B: f64 = 1.0 / 3.0;
+                const C: f64 = 1.0 / 3.0;
+
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x.powi(3)
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x.powi(2)
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-B - 6.0 * C) * x.powi(3)
+                        + (6.0 * B + 30.0 * C) * x.powi(2)
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Returns the largest size with the same aspect ratio as `size` that fits within
+/// `max_size` on both sides, or `None` if `size` already fits.
+pub(crate) fn fit_within(size: (u32, u32), max_size: u32) -> Option<(u32, u32)> {
+    let (width, height) = size;
+    if width <= max_size && height <= max_size {
+        return None;
+    }
+
+    let scale = max_size as f64 / width.max(height) as f64;
+    Some((
+        ((width as f64 * scale).round() as u32).clamp(1, max_size),
+        ((height as f64 * scale).round() as u32).clamp(1, max_size),
+    ))
+}
+
+// The source pixels and their weights contributing to one destination pixel
+struct Contribution {
+    start: usize,
+    weights: Vec<f64>,
+}
+
+fn contributions(src_len: u32, dst_len: u32, filter: ResizeFilter) -> Vec<Contribution> {
+    let scale = dst_len as f64 / src_len as f64;
+
+    // Widen the filter when shrinking so every source pixel contributes
+    let filter_scale = (1.0 / scale).max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f64 + 0.5) / scale;
+            let start = ((center - support).floor().max(0.0)) as usize;
+            let end = ((center + support).ceil() as usize).min(src_len as usize);
+
+            let mut weights = (start..end)
+                .map(|j| filter.kernel((j as f64 + 0.5 - center) / filter_scale))
+                .collect::<Vec<_>>();
+
+            let total = weights.iter().sum::<f64>();
+            if total != 0.0 {
+                for w in weights.iter_mut() {
+                    *w /= total;
+                }
+            }
+
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// Resamples an image to a new size.
+pub(crate) fn resize(image: &Image, new_size: (u32, u32), filter: ResizeFilter) -> Image {
+    let (src_width, src_height) = image.size();
+    let (dst_width, dst_height) = new_size;
+
+    // Premultiply alpha so transparent pixels don't bleed their color into their neighbors
+    let src = image
+        .data()
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3] as f64 / 255.0;
+            [
+                p[0] as f64 * a,
+                p[1] as f64 * a,
+                p[2] as f64 * a,
+                p[3] as f64,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    // Resize horizontally into a dst_width * src_height buffer
+    let columns = contributions(src_width, dst_width, filter);
+    let mut horizontal = vec![0.0; dst_width as usize * src_height as usize * 4];
+    for y in 0..src_height as usize {
+        let row = &src[y * src_width as usize * 4..];
+        for (x, c) in columns.iter().enumerate() {
+            let out = &mut horizontal[(y * dst_width as usize + x) * 4..][..4];
+            for (i, w) in c.weights.iter().enumerate() {
+                let p = &row[(c.start + i) * 4..][..4];
+                for channel in 0..4 {
+                    out[channel] += p[channel] * w;
+                }
+            }
+        }
+    }
+
+    // Then resize vertically
+    let rows = contributions(src_height, dst_height, filter);
+    let mut data = Vec::with_capacity(dst_width as usize * dst_height as usize * 4);
+    for c in &rows {
+        for x in 0..dst_width as usize {
+            let mut sum = [0.0; 4];
+            for (i, w) in c.weights.iter().enumerate() {
+                let p = &horizontal[((c.start + i) * dst_width as usize + x) * 4..][..4];
+                for channel in 0..4 {
+                    sum[channel] += p[channel] * w;
+                }
+            }
+
+            let a = sum[3].clamp(0.0, 255.0);
+            let unpremultiply = |v: f64| {
+                if a > 0.0 {
+                    (v * 255.0 / a).round().clamp(0.0, 255.0) as u8
+                } else {
+                    0
+                }
+            };
+
+            data.extend([
+                unpremultiply(sum[0]),
+                unpremultiply(sum[1]),
+                unpremultiply(sum[2]),
+                a.round() as u8,
+            ]);
+        }
+    }
+
+    Image::new_rgba8(new_size, data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    #[test]
+    fn fits_within() {
+        assert_eq!(fit_within((4096, 2048), 1024), Some((1024, 512)));
+        assert_eq!(fit_within((1000, 3000), 1024), Some((341, 1024)));
+        assert_eq!(fit_within((1024, 1024), 1024), None);
+        assert_eq!(fit_within((5000, 1), 1024), Some((1024, 1)));
+    }
+
+    #[test]
+    fn solid_color() {
+        for filter in [ResizeFilter::Lanczos3, ResizeFilter::Mitchell] {
+            let mut image = Image::new_empty_rgba8((40, 20));
+            for y in 0..20 {
+                for x in 0..40 {
+                    image.set_pixel((x, y), Pixel::new(10, 200, 30, 255));
+                }
+            }
+
+            let resized = resize(&image, (7, 3), filter);
+            assert_eq!(resized.size(), (7, 3));
+            for y in 0..3 {
+                for x in 0..7 {
+                    assert_eq!(resized.get_pixel((x, y)), Pixel::new(10, 200, 30, 255));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_neighbors() {
+        // Mostly transparent black, ending in opaque red
+        let mut image = Image::new_empty_rgba8((16, 1));
+        for x in 12..16 {
+            image.set_pixel((x, 0), Pixel::new(255, 0, 0, 255));
+        }
+
+        let resized = resize(&image, (4, 1), ResizeFilter::Mitchell);
+        let edge = resized.get_pixel((3, 0));
+
+        // The transparent pixels only reduce alpha, they don't darken the color
+        assert_eq!((edge.r, edge.g, edge.b), (255, 0, 0));
+        assert_eq!(resized.get_pixel((0, 0)).a, 0);
+    }
+}
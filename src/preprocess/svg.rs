@@ -0,0 +1,74 @@
+use resvg::{tiny_skia, usvg};
+
+use crate::config::InputConfig;
+
+use super::image::{DecodeError, Image};
+
+/// Renders an SVG at the size configured by its input.
+/// Text is not rendered since no fonts are loaded.
+pub(super) fn rasterize(contents: &[u8], input: &InputConfig) -> Result<Image, DecodeError> {
+    let tree = usvg::Tree::from_data(contents, &usvg::Options::default())?;
+
+    let size = tree.size();
+    let scale = match input.svg_size {
+        Some(svg_size) => svg_size as f32 / size.width().max(size.height()),
+        None => input.svg_scale.unwrap_or(1.0),
+    };
+
+    let width = ((size.width() * scale).round() as u32).max(1);
+    let height = ((size.height() * scale).round() as u32).max(1);
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or(DecodeError::Malformed("SVG is too large to render"))?;
+
+    // Stretch to the rounded size so the image fills every pixel
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // Pixmaps are premultiplied
+    let data: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+
+    Ok(Image::new_rgba8((width, height), data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    const SQUARE: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="4">
+        <rect x="0" y="0" width="4" height="4" fill="red" />
+    </svg>"#;
+
+    #[test]
+    fn sizes() {
+        let image = rasterize(SQUARE, &InputConfig::default()).unwrap();
+        assert_eq!(image.size(), (8, 4));
+        assert_eq!(image.get_pixel((1, 1)), Pixel::new(255, 0, 0, 255));
+        assert_eq!(image.get_pixel((6, 1)).a, 0);
+
+        let input = InputConfig {
+            svg_scale: Some(2.5),
+            ..Default::default()
+        };
+        assert_eq!(rasterize(SQUARE, &input).unwrap().size(), (20, 10));
+
+        let input = InputConfig {
+            svg_size: Some(64),
+            ..Default::default()
+        };
+        let image = rasterize(SQUARE, &input).unwrap();
+        assert_eq!(image.size(), (64, 32));
+        assert_eq!(image.get_pixel((16, 16)), Pixel::new(255, 0, 0, 255));
+    }
+}
@@ -0,0 +1,80 @@
+use super::image::Image;
+
+/// An image with its transparent borders removed.
+pub(super) struct Trimmed {
+    pub image: Image,
+
+    /// Where the kept area starts in the original image.
+    pub offset: (u32, u32),
+}
+
+/// Crops fully transparent rows and columns from the edges of an image, keeping up to
+/// `padding` of them on each side. Returns `None` if there's nothing to crop or every pixel
+/// is transparent.
+pub(super) fn trim(image: &Image, padding: u32) -> Option<Trimmed> {
+    let (width, height) = image.size();
+
+    let mut min = (u32::MAX, u32::MAX);
+    let mut max = (0, 0);
+    for (i, pixel) in image.data().chunks_exact(4).enumerate() {
+        if pixel[3] > 0 {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+    }
+
+    if min.0 > max.0 {
+        return None;
+    }
+
+    let start = (min.0.saturating_sub(padding), min.1.saturating_sub(padding));
+    let end = (
+        (max.0 + 1).saturating_add(padding).min(width),
+        (max.1 + 1).saturating_add(padding).min(height),
+    );
+    let size = (end.0 - start.0, end.1 - start.1);
+
+    if size == (width, height) {
+        return None;
+    }
+
+    Some(Trimmed {
+        image: image.crop(start, size),
+        offset: start,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::image::Pixel;
+
+    #[test]
+    fn borders() {
+        let mut image = Image::new_empty_rgba8((16, 8));
+        image.set_pixel((4, 2), Pixel::new(255, 0, 0, 255));
+        image.set_pixel((6, 3), Pixel::new(0, 0, 255, 128));
+
+        let trimmed = trim(&image, 0).unwrap();
+        assert_eq!(trimmed.offset, (4, 2));
+        assert_eq!(trimmed.image.size(), (3, 2));
+        assert_eq!(trimmed.image.get_pixel((0, 0)), Pixel::new(255, 0, 0, 255));
+        assert_eq!(trimmed.image.get_pixel((2, 1)), Pixel::new(0, 0, 255, 128));
+
+        // Padding stops at the edges of the image
+        let trimmed = trim(&image, 3).unwrap();
+        assert_eq!(trimmed.offset, (1, 0));
+        assert_eq!(trimmed.image.size(), (9, 7));
+    }
+
+    #[test]
+    fn nothing_to_trim() {
+        let mut image = Image::new_empty_rgba8((4, 4));
+        assert!(trim(&image, 1).is_none());
+
+        image.set_pixel((0, 0), Pixel::new(0, 0, 0, 255));
+        image.set_pixel((3, 3), Pixel::new(0, 0, 0, 255));
+        assert!(trim(&image, 1).is_none());
+    }
+}
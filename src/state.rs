@@ -93,6 +93,10 @@ impl State {
         for (ident, asset) in main_state.assets.iter_mut() {
             if let Some(local_asset) = local_state.assets.get(ident) {
                 asset.targets.extend(local_asset.targets.clone());
+
+                if asset.metadata.is_none() {
+                    asset.metadata = local_asset.metadata.clone();
+                }
             }
         }
 
@@ -147,6 +151,51 @@ impl State {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetState {
     pub targets: HashMap<String, TargetState>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AssetMetadata>,
+
+    // Set for images from inputs with `dpi_scales`, which codegen groups by density
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dpi_scale: Option<u32>,
+}
+
+// Measurements of an asset's contents after preprocessing, shared by all targets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssetMetadata {
+    // Hash of the source file that was measured
+    pub hash: String,
+
+    // Size in bytes
+    pub size: u64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+
+    // Duration of audio in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+
+    // Size of the source image if it was shrunk to the input's `max_size` or trimmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_width: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_height: Option<u32>,
+
+    // Processed width divided by the width before shrinking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+
+    // Where the kept area starts in the source image if transparent borders were trimmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim_x: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim_y: Option<u32>,
 }
 
 // For each asset and target pair, store the hash and ID of the last upload.
@@ -158,6 +207,28 @@ pub struct TargetState {
 
     // Used by local syncs to store the previous path and check that the file exists
     pub local_path: Option<PathBuf>,
+
+    // Unix time in seconds of the last upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_at: Option<u64>,
+
+    // Set if the asset was packed into a spritesheet, in which case `id` is the spritesheet's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<Sprite>,
+
+    // Set if the asset is from an input with `pack` but was synced on its own,
+    // e.g. because it's too large for a spritesheet
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unpacked: bool,
+}
+
+// The area of a spritesheet that a packed image was placed in, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sprite {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Debug, Error)]